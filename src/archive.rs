@@ -0,0 +1,243 @@
+//! Module to read and write shapefiles stored in zip archives
+//!
+//! Requires the `zip` feature.
+//!
+//! An archive can hold several shapefiles (*layers*), each layer is named after
+//! the path of its .shp in the archive, without the extension (e.g. `data/roads`).
+//! The .shx, .dbf, .prj, .cpg and spatial indexes of a layer are found
+//! regardless of the case of their name and extension.
+//!
+//! # Example
+//!
+//! ```
+//! use shapefile::archive;
+//!
+//! archive::write_zip("tests/data/polygon_hole.shp", "polygon_hole.zip").unwrap();
+//! assert_eq!(archive::layers("polygon_hole.zip").unwrap(), vec!["polygon_hole"]);
+//!
+//! // With a single layer, its name is not needed
+//! let reader = shapefile::Reader::from_zip("polygon_hole.zip", None).unwrap();
+//! let polygons = reader.read_as::<shapefile::Polygon>().unwrap();
+//! # std::fs::remove_file("polygon_hole.zip").unwrap();
+//! ```
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Cursor, Read, Seek, Write};
+use std::path::Path;
+
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use {Error, Reader};
+
+/// Extensions of the files that are part of a layer, besides the .shp
+const SIDECAR_EXTENSIONS: [&str; 8] = ["shx", "dbf", "prj", "cpg", "qix", "sbn", "sbx", "xml"];
+
+/// Splits the name of a file in the archive in its name without extension, and its extension
+fn split_extension(name: &str) -> Option<(&str, &str)> {
+    let dot = name.rfind('.')?;
+    if name[dot..].contains('/') {
+        None
+    } else {
+        Some((&name[..dot], &name[dot + 1..]))
+    }
+}
+
+fn layers_of<R: Read + Seek>(archive: &ZipArchive<R>) -> Vec<String> {
+    let mut layers = archive
+        .file_names()
+        .filter_map(split_extension)
+        .filter(|(_, extension)| extension.eq_ignore_ascii_case("shp"))
+        .map(|(layer, _)| layer.to_string())
+        .collect::<Vec<_>>();
+    layers.sort();
+    layers
+}
+
+/// Returns the name of the layers of the archive, sorted
+pub fn layers<P: AsRef<Path>>(path: P) -> Result<Vec<String>, Error> {
+    let archive =
+        ZipArchive::new(BufReader::new(File::open(path)?)).map_err(std::io::Error::from)?;
+    Ok(layers_of(&archive))
+}
+
+/// Finds the layer matching the requested name, either the full name of the layer
+/// or only its file name, ignoring the case
+fn find_layer(layers: &[String], requested: Option<&str>) -> Result<String, Error> {
+    let not_found = || Error::LayerNotFound {
+        requested: requested.map(|name| name.to_string()),
+        available: layers.to_vec(),
+    };
+    let requested = match requested {
+        Some(requested) => requested,
+        None if layers.len() == 1 => return Ok(layers[0].clone()),
+        None => return Err(not_found()),
+    };
+
+    if let Some(layer) = layers.iter().find(|l| l.eq_ignore_ascii_case(requested)) {
+        return Ok(layer.clone());
+    }
+    let mut matching = layers.iter().filter(|layer| {
+        let file_name = layer.rsplit('/').next().unwrap_or(layer);
+        file_name.eq_ignore_ascii_case(requested)
+    });
+    match (matching.next(), matching.next()) {
+        (Some(layer), None) => Ok(layer.clone()),
+        _ => Err(not_found()),
+    }
+}
+
+/// Returns the content of the file of the layer with the given extension, if it exists
+fn read_file_of_layer<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    layer: &str,
+    extension: &str,
+) -> Result<Option<Vec<u8>>, Error> {
+    let names = archive
+        .file_names()
+        .filter(|name| match split_extension(name) {
+            Some((stem, ext)) => {
+                stem.eq_ignore_ascii_case(layer) && ext.eq_ignore_ascii_case(extension)
+            }
+            None => false,
+        })
+        .collect::<Vec<_>>();
+    // The file whose name has the case of the layer is preferred
+    let name = names
+        .iter()
+        .find(|name| name.starts_with(layer))
+        .or_else(|| names.first());
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => return Ok(None),
+    };
+    let mut file = archive.by_name(&name).map_err(std::io::Error::from)?;
+    // The size of the entry is not trusted to pre-allocate
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)?;
+    Ok(Some(content))
+}
+
+impl Reader<Cursor<Vec<u8>>> {
+    /// Creates a reader from a layer of a zip archive
+    ///
+    /// Like [from_path](struct.Reader.html#method.from_path) the .shx, .dbf, .prj, .cpg
+    /// and spatial indexes of the layer are also read if they exist.
+    /// The files of the layer are decompressed in memory.
+    ///
+    /// The `layer_name` can be omitted when the archive only holds one shapefile
+    /// (see the [archive](../archive/index.html) module for the naming of the layers).
+    ///
+    /// Requires the `zip` feature.
+    ///
+    /// # Errors
+    ///
+    /// `Error::LayerNotFound`, with the layers of the archive, if the layer is not
+    /// in the archive, or if no name was given and the archive holds several layers.
+    pub fn from_zip<P: AsRef<Path>>(path: P, layer_name: Option<&str>) -> Result<Self, Error> {
+        let mut archive =
+            ZipArchive::new(BufReader::new(File::open(path)?)).map_err(std::io::Error::from)?;
+        let layer = find_layer(&layers_of(&archive), layer_name)?;
+
+        let shp = read_file_of_layer(&mut archive, &layer, "shp")?.unwrap_or_default();
+        let mut reader = Self::new(Cursor::new(shp))?;
+
+        if let Some(shx) = read_file_of_layer(&mut archive, &layer, "shx")? {
+            reader.add_index_source(shx.as_slice())?;
+        }
+
+        if let Some(sbn) = read_file_of_layer(&mut archive, &layer, "sbn")? {
+            // Like with from_path, a .sbn that cannot be read is ignored
+            let _ = reader.add_spatial_index_source(sbn.as_slice());
+        }
+
+        if let Some(qix) = read_file_of_layer(&mut archive, &layer, "qix")? {
+            // Like with from_path, a .qix that cannot be read is ignored
+            let _ = reader.add_quad_tree_source(qix.as_slice());
+        }
+
+        if let Some(prj) = read_file_of_layer(&mut archive, &layer, "prj")? {
+            // Like with from_path, a .prj that cannot be read is ignored
+            let _ = reader.add_projection_source(prj.as_slice());
+        }
+
+        if let Some(dbf) = read_file_of_layer(&mut archive, &layer, "dbf")? {
+            reader.add_dbf_source(Cursor::new(dbf))?;
+        }
+
+        if let Some(cpg) = read_file_of_layer(&mut archive, &layer, "cpg")? {
+            reader.set_dbf_encoding_from_cpg(cpg.as_slice())?;
+        }
+        Ok(reader)
+    }
+}
+
+/// Writes the shapefile at `shapefile_path` and the files next to it
+/// (.shx, .dbf, .prj, .cpg, spatial indexes) in a new zip archive
+///
+/// The files are written at the root of the archive, the name of the layer
+/// is the one of the .shp.
+///
+/// This allows to distribute the files written by any of the writers
+/// (see the [writer](../writer/index.html) module) as a single archive.
+///
+/// Requires the `zip` feature.
+pub fn write_zip<P: AsRef<Path>, Z: AsRef<Path>>(
+    shapefile_path: P,
+    zip_path: Z,
+) -> Result<(), Error> {
+    let shapefile_path = shapefile_path.as_ref();
+    let mut zip_writer = ZipWriter::new(BufWriter::new(File::create(zip_path)?));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut paths = vec![shapefile_path.to_path_buf()];
+    paths.extend(
+        SIDECAR_EXTENSIONS
+            .iter()
+            .map(|extension| shapefile_path.with_extension(extension))
+            .filter(|path| path.exists()),
+    );
+    for path in paths {
+        let file_name = match path.file_name() {
+            Some(file_name) => file_name.to_string_lossy().into_owned(),
+            None => continue,
+        };
+        zip_writer
+            .start_file(file_name, options)
+            .map_err(std::io::Error::from)?;
+        std::io::copy(&mut File::open(&path)?, &mut zip_writer)?;
+    }
+    zip_writer.finish().map_err(std::io::Error::from)?.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_layer_by_name() {
+        let layers = vec!["data/Roads".to_string(), "rivers".to_string()];
+        assert_eq!(
+            find_layer(&layers, Some("data/roads")).unwrap(),
+            "data/Roads"
+        );
+        assert_eq!(find_layer(&layers, Some("ROADS")).unwrap(), "data/Roads");
+        assert_eq!(find_layer(&layers, Some("rivers")).unwrap(), "rivers");
+
+        for requested in &[None, Some("lakes")] {
+            match find_layer(&layers, *requested) {
+                Err(Error::LayerNotFound { available, .. }) => assert_eq!(available, layers),
+                other => panic!("Unexpected result: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn split_extension_of_names() {
+        assert_eq!(
+            split_extension("data/roads.SHP"),
+            Some(("data/roads", "SHP"))
+        );
+        assert_eq!(split_extension("data.v2/roads"), None);
+    }
+}
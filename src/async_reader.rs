@@ -0,0 +1,405 @@
+//! Module with the definition of the [AsyncReader](struct.AsyncReader.html) that reads
+//! shapefiles from `tokio::io::AsyncRead` sources
+//!
+//! Requires the `async` feature.
+//!
+//! The headers, shapes and records are read in memory without blocking,
+//! and then decoded like the [Reader](../reader/struct.Reader.html) does.
+//! The shapes (and their records) are given by a `Stream`.
+//!
+//! # Example
+//!
+//! ```
+//! extern crate futures;
+//! # extern crate shapefile;
+//! use futures::executor::block_on;
+//! use futures::{StreamExt, TryFutureExt};
+//! use shapefile::async_reader::AsyncReader;
+//!
+//! # fn main() {
+//! let shp = std::fs::read("tests/data/multipatch.shp").unwrap();
+//! let dbf = std::fs::read("tests/data/multipatch.dbf").unwrap();
+//!
+//! let reader = block_on(
+//!     AsyncReader::new(shp.as_slice()).and_then(|reader| reader.with_dbf_source(dbf.as_slice())),
+//! )
+//! .unwrap();
+//! let features = block_on(reader.into_shapes_and_records().unwrap().collect::<Vec<_>>());
+//! assert_eq!(features.len(), 1);
+//! # }
+//! ```
+use std::future::{poll_fn, Future};
+use std::io;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use tokio::io::{AsyncRead, ReadBuf};
+
+use dbf::TableHeader;
+use header;
+use record::{ReadableShape, RecordHeader};
+use {Error, FileRole, Shape};
+
+/// Max number of bytes the buffer of a `ShapeStream` grows by before they are read
+const READ_STEP: usize = 64 * 1024;
+
+/// Reads from the source until `buffer[*filled..]` is full
+///
+/// `filled` is kept up to date so that reading can resume after `Poll::Pending`.
+fn poll_fill<T: AsyncRead + Unpin>(
+    source: &mut T,
+    cx: &mut Context,
+    buffer: &mut [u8],
+    filled: &mut usize,
+) -> Poll<io::Result<()>> {
+    while *filled < buffer.len() {
+        let mut read_buf = ReadBuf::new(&mut buffer[*filled..]);
+        match Pin::new(&mut *source).poll_read(cx, &mut read_buf) {
+            Poll::Ready(Ok(())) => {}
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        }
+        let num_read = read_buf.filled().len();
+        if num_read == 0 {
+            return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+        }
+        *filled += num_read;
+    }
+    Poll::Ready(Ok(()))
+}
+
+/// Reads from the source until the buffer holds `len` bytes
+///
+/// Unlike `poll_fill`, the buffer grows with the bytes actually read
+/// (`READ_STEP` at most before each read), so that a length read from
+/// the source does not allocate for bytes that never come.
+fn poll_fill_to<T: AsyncRead + Unpin>(
+    source: &mut T,
+    cx: &mut Context,
+    buffer: &mut Vec<u8>,
+    len: usize,
+) -> Poll<io::Result<()>> {
+    while buffer.len() < len {
+        let filled = buffer.len();
+        buffer.resize(filled + (len - filled).min(READ_STEP), 0);
+        let mut read_buf = ReadBuf::new(&mut buffer[filled..]);
+        let result = Pin::new(&mut *source).poll_read(cx, &mut read_buf);
+        let num_read = read_buf.filled().len();
+        buffer.truncate(filled + num_read);
+        match result {
+            Poll::Ready(Ok(())) if num_read == 0 => {
+                return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()))
+            }
+            Poll::Ready(Ok(())) => {}
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        }
+    }
+    Poll::Ready(Ok(()))
+}
+
+/// The .dbf source and its header
+struct AsyncTable<D> {
+    source: D,
+    header: TableHeader,
+    buffer: Vec<u8>,
+    filled: usize,
+    current_record: usize,
+}
+
+impl<D: AsyncRead + Unpin> AsyncTable<D> {
+    fn poll_next_record(&mut self, cx: &mut Context) -> Poll<Option<Result<dbase::Record, Error>>> {
+        if self.current_record >= self.header.num_records() {
+            return Poll::Ready(None);
+        }
+        let index = self.current_record;
+        let offset = (self.header.size() + index * self.header.record_size()) as u64;
+        self.buffer.resize(self.header.record_size(), 0);
+        let record = match poll_fill(&mut self.source, cx, &mut self.buffer, &mut self.filled) {
+            Poll::Ready(Ok(())) => {
+                self.filled = 0;
+                self.current_record += 1;
+                self.header.decode_record(&self.buffer)
+            }
+            Poll::Ready(Err(e)) => {
+                // The position of the next record is unknown
+                self.current_record = self.header.num_records();
+                Err(Error::IoError(e))
+            }
+            Poll::Pending => return Poll::Pending,
+        };
+        Poll::Ready(Some(record.map_err(|e| {
+            e.with_context(FileRole::Dbf, Some(index), Some(offset))
+        })))
+    }
+}
+
+/// Stream over the shapes of a .shp file
+pub struct ShapeStream<T, S> {
+    _shape: PhantomData<fn() -> S>,
+    source: T,
+    buffer: Vec<u8>,
+    /// Size of the content of the record being read, `None` while reading its header
+    record_size: Option<usize>,
+    /// Index of the record being read
+    current_index: usize,
+    current_pos: usize,
+    file_length: usize,
+}
+
+impl<T: AsyncRead + Unpin, S: ReadableShape> ShapeStream<T, S> {
+    /// Returns the size in bytes of the content of the record at the current position,
+    /// which must not be negative nor go past the end of the file
+    fn checked_record_size(&self, record_size: i32) -> Result<usize, Error> {
+        let content_start = self.current_pos + RecordHeader::SIZE;
+        if record_size < 0 || content_start + record_size as usize * 2 > self.file_length {
+            return Err(Error::InvalidShapeRecordSize);
+        }
+        Ok(record_size as usize * 2)
+    }
+
+    fn poll_next_shape(&mut self, cx: &mut Context) -> Poll<Option<Result<S, Error>>> {
+        loop {
+            if self.record_size.is_none() && self.current_pos >= self.file_length {
+                return Poll::Ready(None);
+            }
+            let index = Some(self.current_index);
+            let offset = Some(self.current_pos as u64);
+            let len = self.record_size.unwrap_or(RecordHeader::SIZE);
+            match poll_fill_to(&mut self.source, cx, &mut self.buffer, len) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(e)) => {
+                    // The position of the next record is unknown
+                    self.record_size = None;
+                    self.buffer.clear();
+                    self.current_pos = self.file_length;
+                    let error = Error::IoError(e).with_context(FileRole::Shp, index, offset);
+                    return Poll::Ready(Some(Err(error)));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+
+            match self.record_size.take() {
+                None => {
+                    let record_size = RecordHeader::read_from(&mut self.buffer.as_slice())
+                        .and_then(|hdr| self.checked_record_size(hdr.record_size));
+                    self.buffer.clear();
+                    let record_size = match record_size {
+                        Ok(record_size) => record_size,
+                        Err(e) => {
+                            // The position of the next record cannot be trusted
+                            self.current_pos = self.file_length;
+                            let error = e.with_context(FileRole::Shp, index, offset);
+                            return Poll::Ready(Some(Err(error)));
+                        }
+                    };
+                    self.record_size = Some(record_size);
+                }
+                Some(record_size) => {
+                    self.current_index += 1;
+                    self.current_pos += RecordHeader::SIZE + record_size;
+                    let shape = S::read_from(&mut self.buffer.as_slice(), record_size as i32)
+                        .map_err(|e| e.with_context(FileRole::Shp, index, offset));
+                    self.buffer.clear();
+                    return Poll::Ready(Some(shape));
+                }
+            }
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin, S: ReadableShape> Stream for ShapeStream<T, S> {
+    type Item = Result<S, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_next_shape(cx)
+    }
+}
+
+/// Stream over the shapes of a .shp file and their records
+pub struct ShapeRecordStream<T, S, D = T> {
+    shapes: ShapeStream<T, S>,
+    table: AsyncTable<D>,
+    /// Shape (or error while reading it) whose record is being read
+    shape: Option<Result<S, Error>>,
+}
+
+impl<T, S, D> Stream for ShapeRecordStream<T, S, D>
+where
+    T: AsyncRead + Unpin,
+    S: ReadableShape + Unpin,
+    D: AsyncRead + Unpin,
+{
+    type Item = Result<(S, dbase::Record), Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.shape.is_none() {
+            match this.shapes.poll_next_shape(cx) {
+                Poll::Ready(Some(shape)) => this.shape = Some(shape),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        // The record is read even if the shape could not be,
+        // so that the next shapes stay paired with their records
+        match this.table.poll_next_record(cx) {
+            Poll::Ready(Some(record)) => {
+                let shape = this.shape.take().unwrap();
+                Poll::Ready(Some(shape.and_then(|shape| Ok((shape, record?)))))
+            }
+            Poll::Ready(None) => match this.shape.take().unwrap() {
+                Err(e) => Poll::Ready(Some(Err(e))),
+                Ok(_) => Poll::Ready(None),
+            },
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// struct that reads the content of a shapefile from `AsyncRead` sources
+///
+/// `T` is the type of the *shp* source and `D` the type of the *dbf* source.
+pub struct AsyncReader<T, D = T> {
+    source: T,
+    header: header::Header,
+    table: Option<AsyncTable<D>>,
+}
+
+impl<T: AsyncRead + Unpin> AsyncReader<T> {
+    /// Returns a future that reads the header of the shapefile
+    /// and then resolves to the reader
+    ///
+    /// # Errors
+    ///
+    /// Like [Reader::new](../reader/struct.Reader.html#method.new),
+    /// fails if the header is not the one of a shapefile
+    pub fn new(source: T) -> impl Future<Output = Result<Self, Error>> {
+        let mut buffer = vec![0u8; header::HEADER_SIZE as usize];
+        let mut filled = 0;
+        let mut source = Some(source);
+        poll_fn(move |cx| {
+            let result = match source {
+                Some(ref mut source) => poll_fill(source, cx, &mut buffer, &mut filled),
+                None => panic!("AsyncReader::new polled after completion"),
+            };
+            match result {
+                Poll::Ready(Ok(())) => {
+                    let header = header::Header::read_from(&mut buffer.as_slice())
+                        .map_err(|e| e.with_context(FileRole::Shp, None, Some(0)))?;
+                    Poll::Ready(Ok(AsyncReader {
+                        source: source.take().unwrap(),
+                        header,
+                        table: None,
+                    }))
+                }
+                Poll::Ready(Err(e)) => {
+                    let error = Error::IoError(e).with_context(FileRole::Shp, None, Some(0));
+                    Poll::Ready(Err(error))
+                }
+                Poll::Pending => Poll::Pending,
+            }
+        })
+    }
+}
+
+impl<T: AsyncRead + Unpin, D: AsyncRead + Unpin> AsyncReader<T, D> {
+    /// Returns the header of the .shp
+    pub fn header(&self) -> &header::Header {
+        &self.header
+    }
+
+    /// Returns a future that reads the header of the dbf from `source`
+    /// and then resolves to a reader that will read the records from it
+    pub fn with_dbf_source<D2: AsyncRead + Unpin>(
+        self,
+        source: D2,
+    ) -> impl Future<Output = Result<AsyncReader<T, D2>, Error>> {
+        let mut buffer = vec![0u8; TableHeader::PREFIX_SIZE];
+        let mut filled = 0;
+        let mut parts = Some((self, source));
+        let with_context = |e: Error| e.with_context(FileRole::Dbf, None, Some(0));
+        poll_fn(move |cx| loop {
+            let result = match parts {
+                Some((_, ref mut source)) => poll_fill(source, cx, &mut buffer, &mut filled),
+                None => panic!("AsyncReader::with_dbf_source polled after completion"),
+            };
+            match result {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(with_context(Error::IoError(e)))),
+                Poll::Pending => return Poll::Pending,
+            }
+
+            // The size of the header is known once its prefix is read
+            if buffer.len() == TableHeader::PREFIX_SIZE {
+                let header_size = TableHeader::size_from_prefix(&buffer).map_err(with_context)?;
+                buffer.resize(header_size, 0);
+                continue;
+            }
+            let header =
+                TableHeader::from_bytes(std::mem::take(&mut buffer)).map_err(with_context)?;
+            let (reader, source) = parts.take().unwrap();
+            return Poll::Ready(Ok(AsyncReader {
+                source: reader.source,
+                header: reader.header,
+                table: Some(AsyncTable {
+                    source,
+                    header,
+                    buffer: Vec::new(),
+                    filled: 0,
+                    current_record: 0,
+                }),
+            }));
+        })
+    }
+
+    /// Returns a stream over the shapes of the file,
+    /// which must all be of type `S`
+    ///
+    /// (see [Reader::iter_shapes_as](../reader/struct.Reader.html#method.iter_shapes_as))
+    pub fn into_shapes_as<S: ReadableShape>(self) -> ShapeStream<T, S> {
+        ShapeStream {
+            _shape: PhantomData,
+            source: self.source,
+            buffer: Vec::new(),
+            record_size: None,
+            current_index: 0,
+            current_pos: header::HEADER_SIZE as usize,
+            file_length: self.header.file_size(),
+        }
+    }
+
+    /// Returns a stream over the shapes of the file
+    pub fn into_shapes(self) -> ShapeStream<T, Shape> {
+        self.into_shapes_as::<Shape>()
+    }
+
+    /// Returns a stream over the shapes of the file, which must all be of type `S`,
+    /// and their records
+    ///
+    /// # Errors
+    ///
+    /// `Error::MissingDbf` if no *.dbf* was given
+    pub fn into_shapes_and_records_as<S: ReadableShape>(
+        mut self,
+    ) -> Result<ShapeRecordStream<T, S, D>, Error> {
+        let table = self.table.take().ok_or(Error::MissingDbf)?;
+        Ok(ShapeRecordStream {
+            shapes: self.into_shapes_as::<S>(),
+            table,
+            shape: None,
+        })
+    }
+
+    /// Returns a stream over the shapes of the file and their records
+    ///
+    /// # Errors
+    ///
+    /// `Error::MissingDbf` if no *.dbf* was given
+    pub fn into_shapes_and_records(self) -> Result<ShapeRecordStream<T, Shape, D>, Error> {
+        self.into_shapes_and_records_as::<Shape>()
+    }
+}
@@ -0,0 +1,316 @@
+//! Module with the definition of the [AsyncWriter](struct.AsyncWriter.html) that writes
+//! shapes to `tokio::io::AsyncWrite + AsyncSeek` destinations
+//!
+//! Requires the `async` feature.
+//!
+//! Like the [ShapeWriter](../writer/struct.ShapeWriter.html), shapes are written one at a time
+//! and the header of the .shp is written, as well as the .shx, when the writer is finalized.
+//! As finalizing requires to await, it is **not** done when the writer is dropped.
+//!
+//! # Example
+//!
+//! ```
+//! extern crate futures;
+//! # extern crate shapefile;
+//! use futures::executor::block_on;
+//! use shapefile::async_writer::AsyncWriter;
+//! use shapefile::Point;
+//! use std::io::Cursor;
+//!
+//! # fn main() {
+//! let mut writer = AsyncWriter::new(Cursor::new(Vec::<u8>::new()));
+//! writer.add_index_dest(Cursor::new(Vec::<u8>::new()));
+//! for i in 0..10 {
+//!     block_on(writer.write_shape(&Point::new(i as f64, 0.0))).unwrap();
+//! }
+//! block_on(writer.finalize()).unwrap();
+//! # }
+//! ```
+use std::future::{poll_fn, Future};
+use std::io::{self, SeekFrom};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use tokio::io::{AsyncSeek, AsyncWrite};
+
+use header;
+use index::{write_index_file, ShapeIndex};
+use record::{BBoxZ, EsriShape, RecordHeader};
+use writer::{size_of_record, write_record};
+use {Error, FileRole, ShapeType};
+
+/// Writes `buffer[*written..]` to the dest
+///
+/// `written` is kept up to date so that writing can resume after `Poll::Pending`.
+fn poll_write_all<T: AsyncWrite + Unpin>(
+    dest: &mut T,
+    cx: &mut Context,
+    buffer: &[u8],
+    written: &mut usize,
+) -> Poll<io::Result<()>> {
+    while *written < buffer.len() {
+        let num_written = ready!(Pin::new(&mut *dest).poll_write(cx, &buffer[*written..]))?;
+        if num_written == 0 {
+            return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+        }
+        *written += num_written;
+    }
+    Poll::Ready(Ok(()))
+}
+
+/// Seeks the dest, `started` tells if the seek was already started by a previous poll
+fn poll_seek<T: AsyncSeek + Unpin>(
+    dest: &mut T,
+    cx: &mut Context,
+    position: SeekFrom,
+    started: &mut bool,
+) -> Poll<io::Result<u64>> {
+    if !*started {
+        Pin::new(&mut *dest).start_seek(position)?;
+        *started = true;
+    }
+    let result = ready!(Pin::new(&mut *dest).poll_complete(cx));
+    *started = false;
+    Poll::Ready(result)
+}
+
+/// The steps of the finalization, in order
+#[derive(Copy, Clone, PartialEq)]
+enum FinalizeStep {
+    WritePending,
+    SeekToHeader,
+    WriteHeader,
+    SeekToEnd,
+    FlushShp,
+    WriteIndex,
+    FlushIndex,
+}
+
+/// struct that writes shapes one at a time to `AsyncWrite + AsyncSeek` destinations
+///
+/// The final header of the .shp and the .shx are written by [finalize](#method.finalize),
+/// which has to be awaited, dropping the writer leaves the files incomplete.
+pub struct AsyncWriter<T> {
+    dest: T,
+    index_dest: Option<T>,
+    header: header::Header,
+    shapes_index: Vec<ShapeIndex>,
+    /// The type and bbox of the header are the ones of the non-null shapes
+    has_non_null_shapes: bool,
+    /// Bytes not yet written to the dest
+    buffer: Vec<u8>,
+    written: usize,
+}
+
+impl<T: AsyncWrite + AsyncSeek + Unpin> AsyncWriter<T> {
+    /// Creates a writer that writes shapes one at a time.
+    ///
+    /// The `dest` argument is only for the .shp
+    pub fn new(dest: T) -> Self {
+        Self {
+            dest,
+            index_dest: None,
+            header: header::Header::default(),
+            shapes_index: Vec::new(),
+            has_non_null_shapes: false,
+            buffer: Vec::new(),
+            written: 0,
+        }
+    }
+
+    /// Adds dest as the destination where the index file will be written
+    pub fn add_index_dest(&mut self, dest: T) {
+        self.index_dest = Some(dest);
+    }
+
+    /// Encodes the record of the shape at the end of the buffer
+    fn encode_shape<S: EsriShape>(&mut self, shape: &S) -> Result<(), Error> {
+        let index = self.shapes_index.len();
+        let offset = self.header.file_length as u64 * 2;
+        if offset + size_of_record(shape) > header::MAX_FILE_SIZE {
+            let error = Error::FileTooLarge;
+            return Err(error.with_context(FileRole::Shp, Some(index), Some(offset)));
+        }
+        let is_first = index == 0;
+        let shapetype = shape.record_shapetype();
+        let is_null = shapetype == ShapeType::NullShape;
+        if !is_null && self.has_non_null_shapes && shapetype != self.header.shape_type {
+            let error = Error::MismatchShapeType {
+                requested: self.header.shape_type,
+                actual: shapetype,
+            };
+            return Err(error.with_context(FileRole::Shp, Some(index), Some(offset)));
+        }
+
+        let record_size = (shape.size_in_bytes() + std::mem::size_of::<i32>()) / 2;
+        let rc_hdr = RecordHeader {
+            record_number: index as i32 + 1,
+            record_size: record_size as i32,
+        };
+
+        let buffer_len = self.buffer.len();
+        if is_first {
+            // Placeholder, the real header is written when finalizing
+            self.header.write_to(&mut self.buffer)?;
+        }
+        if let Err(e) = write_record(&mut self.buffer, &rc_hdr, shape) {
+            self.buffer.truncate(buffer_len);
+            return Err(e.with_context(FileRole::Shp, Some(index), Some(offset)));
+        }
+
+        if is_null {
+            if is_first {
+                self.header.shape_type = S::shapetype();
+            }
+        } else if self.has_non_null_shapes {
+            self.header.bbox.grow_from_shape(shape);
+        } else {
+            self.has_non_null_shapes = true;
+            self.header.shape_type = shapetype;
+            self.header.bbox = BBoxZ::from_shape(shape);
+        }
+        self.shapes_index.push(ShapeIndex {
+            offset: self.header.file_length,
+            record_size: record_size as i32,
+        });
+        self.header.file_length += record_size as i32 + RecordHeader::SIZE as i32 / 2;
+        Ok(())
+    }
+
+    /// Writes the bytes of the buffer that were not written yet
+    fn poll_write_pending(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        ready!(poll_write_all(
+            &mut self.dest,
+            cx,
+            &self.buffer,
+            &mut self.written
+        ))?;
+        self.buffer.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+
+    /// Returns a future that writes one shape
+    ///
+    /// The shape is encoded when this method is called, if the future is dropped
+    /// before completion, the rest of its bytes are written with the next shape.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::MismatchShapeType` if the shape type is not the same
+    /// as the one of the shapes previously written,
+    /// and `Error::FileTooLarge` if the shape does not fit in the .shp.
+    pub fn write_shape<S: EsriShape>(&mut self, shape: &S) -> WriteShape<'_, T> {
+        let error = self.encode_shape(shape).err();
+        WriteShape {
+            writer: self,
+            error,
+        }
+    }
+
+    /// Returns a future that writes the final header of the .shp and the content of the .shx
+    ///
+    /// Writing shapes after the writer is finalized is not possible,
+    /// this is why this method consumes the writer.
+    pub fn finalize(mut self) -> impl Future<Output = Result<(), Error>> {
+        // Number of bytes written for the header and the records
+        let shp_size = if self.shapes_index.is_empty() {
+            0
+        } else {
+            i64::from(self.header.file_length) * 2
+        };
+        let mut header_bytes = Vec::with_capacity(header::HEADER_SIZE as usize);
+        let mut index_bytes = Vec::new();
+        let encoded = self
+            .header
+            .write_to(&mut header_bytes)
+            .map_err(Error::IoError)
+            .and_then(|_| {
+                if self.index_dest.is_some() {
+                    let shapes_index = std::mem::take(&mut self.shapes_index);
+                    write_index_file(&mut index_bytes, &self.header, shapes_index)?;
+                }
+                Ok(())
+            });
+
+        let mut step = FinalizeStep::WritePending;
+        let mut written = 0;
+        let mut seek_started = false;
+        let mut encoded = Some(encoded);
+        poll_fn(move |cx| loop {
+            if let Some(encoded) = encoded.take() {
+                encoded?;
+            }
+            match step {
+                FinalizeStep::WritePending => {
+                    ready!(self.poll_write_pending(cx))?;
+                    step = if shp_size == 0 {
+                        // No shapes were written, we still want a valid (empty) shapefile
+                        FinalizeStep::WriteHeader
+                    } else {
+                        FinalizeStep::SeekToHeader
+                    };
+                }
+                FinalizeStep::SeekToHeader => {
+                    let position = SeekFrom::Current(-shp_size);
+                    ready!(poll_seek(&mut self.dest, cx, position, &mut seek_started))?;
+                    step = FinalizeStep::WriteHeader;
+                }
+                FinalizeStep::WriteHeader => {
+                    ready!(poll_write_all(
+                        &mut self.dest,
+                        cx,
+                        &header_bytes,
+                        &mut written
+                    ))?;
+                    written = 0;
+                    step = if shp_size == 0 {
+                        FinalizeStep::FlushShp
+                    } else {
+                        FinalizeStep::SeekToEnd
+                    };
+                }
+                FinalizeStep::SeekToEnd => {
+                    let position = SeekFrom::Current(shp_size - header::HEADER_SIZE as i64);
+                    ready!(poll_seek(&mut self.dest, cx, position, &mut seek_started))?;
+                    step = FinalizeStep::FlushShp;
+                }
+                FinalizeStep::FlushShp => {
+                    ready!(Pin::new(&mut self.dest).poll_flush(cx))?;
+                    step = FinalizeStep::WriteIndex;
+                }
+                FinalizeStep::WriteIndex => {
+                    if let Some(ref mut shx_dest) = self.index_dest {
+                        ready!(poll_write_all(shx_dest, cx, &index_bytes, &mut written))?;
+                    }
+                    step = FinalizeStep::FlushIndex;
+                }
+                FinalizeStep::FlushIndex => {
+                    if let Some(ref mut shx_dest) = self.index_dest {
+                        ready!(Pin::new(shx_dest).poll_flush(cx))?;
+                    }
+                    return Poll::Ready(Ok(()));
+                }
+            }
+        })
+    }
+}
+
+/// Future returned by [AsyncWriter::write_shape](struct.AsyncWriter.html#method.write_shape)
+pub struct WriteShape<'a, T> {
+    writer: &'a mut AsyncWriter<T>,
+    error: Option<Error>,
+}
+
+impl<'a, T: AsyncWrite + AsyncSeek + Unpin> Future for WriteShape<'a, T> {
+    type Output = Result<(), Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if let Some(error) = this.error.take() {
+            return Poll::Ready(Err(error));
+        }
+        ready!(this.writer.poll_write_pending(cx))?;
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,1025 @@
+//! Module to read and write the .dbf file of a shapefile
+//!
+//! Records are read one at a time by the [Reader](../reader/struct.Reader.html),
+//! either sequentially or by index when the source implements `Seek`.
+//!
+//! The writer of the [dbase](../../dbase/index.html) crate deduces the fields
+//! from the records it has to write, which means all the records have to be known upfront.
+//!
+//! The [TableWriter](struct.TableWriter.html) of this module instead takes the
+//! list of fields once, and then writes the records one at a time, checking
+//! that each of them matches the declared fields.
+//!
+//! By default, the text of the records is read and written as UTF-8.
+//! The encoding actually used is declared in the .cpg file next to the .dbf
+//! (see [encoding_from_cpg](fn.encoding_from_cpg.html)), the text is then decoded
+//! with that encoding, and the [TableWriter](struct.TableWriter.html) can be set
+//! to encode it with another one.
+//!
+//! # Example
+//!
+//! ```
+//! use std::io::Cursor;
+//! use shapefile::dbase::{FieldValue, Record};
+//! use shapefile::dbf::{FieldInfo, TableWriter};
+//!
+//! let fields = vec![
+//!     FieldInfo::character("NAME", 20).unwrap(),
+//!     FieldInfo::numeric("POPULATION", 10, 0).unwrap(),
+//! ];
+//! let mut writer = TableWriter::new(Cursor::new(Vec::<u8>::new()), fields).unwrap();
+//!
+//! let mut record = Record::new();
+//! record.insert("NAME".to_string(), FieldValue::Character(Some("Lyon".to_string())));
+//! record.insert("POPULATION".to_string(), FieldValue::Numeric(Some(513_275.0)));
+//! writer.write_record(&record).unwrap();
+//!
+//! // A value that does not match the field type is an error
+//! record.insert("POPULATION".to_string(), FieldValue::Character(None));
+//! assert!(writer.write_record(&record).is_err());
+//!
+//! writer.finalize().unwrap();
+//! ```
+use std::fmt;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use dbase::{FieldValue, Record};
+use encoding_rs::{Encoding, UTF_8};
+
+use {Error, FileRole};
+
+/// Size of the header of a .dbf file (without the field descriptors)
+const TABLE_HEADER_SIZE: usize = 32;
+/// Size of each field descriptor
+const FIELD_DESCRIPTOR_SIZE: usize = 32;
+/// Max number of bytes of a field name
+const FIELD_NAME_MAX_LEN: usize = 10;
+/// dBase III without memo
+const TABLE_VERSION: u8 = 0x03;
+/// Byte written after the last field descriptor
+const HEADER_TERMINATOR: u8 = 0x0D;
+/// Byte written after the last record
+const FILE_TERMINATOR: u8 = 0x1A;
+/// Value of the deletion flag for records that are not deleted
+const RECORD_NOT_DELETED: u8 = b' ';
+/// Date of the last update (year minus 1900, month, day),
+/// the same as the one written by the writer of the dbase crate
+const LAST_UPDATE: [u8; 3] = [90, 12, 25];
+
+/// Returns the encoding declared by the content of a .cpg file
+///
+/// The content can either be a code page number (e.g. `1252`, `65001`)
+/// or the label of an encoding (e.g. `UTF-8`, `ISO-8859-1`).
+///
+/// Returns `None` if the encoding is not known.
+///
+/// # Example
+///
+/// ```
+/// use shapefile::dbf::encoding_from_cpg;
+/// use shapefile::encoding_rs::{UTF_8, WINDOWS_1252};
+///
+/// assert_eq!(encoding_from_cpg("1252"), Some(WINDOWS_1252));
+/// assert_eq!(encoding_from_cpg("UTF-8\r\n"), Some(UTF_8));
+/// assert_eq!(encoding_from_cpg("8859_1"), Some(WINDOWS_1252));
+/// assert_eq!(encoding_from_cpg("not an encoding"), None);
+/// ```
+pub fn encoding_from_cpg(content: &str) -> Option<&'static Encoding> {
+    let content = content.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    let label = content.trim_start_matches("ANSI ");
+    if let Ok(code_page) = label.parse::<u32>() {
+        let label = match code_page {
+            65001 => "utf-8".to_string(),
+            874 | 1250..=1258 => format!("windows-{}", code_page),
+            866 => "ibm866".to_string(),
+            932 => "shift_jis".to_string(),
+            936 => "gbk".to_string(),
+            949 => "euc-kr".to_string(),
+            950 => "big5".to_string(),
+            20866 => "koi8-r".to_string(),
+            21866 => "koi8-u".to_string(),
+            28591..=28606 => format!("iso-8859-{}", code_page - 28590),
+            _ => return None,
+        };
+        Encoding::for_label(label.as_bytes())
+    } else if label.starts_with("8859") {
+        Encoding::for_label(format!("iso-{}", label.replace('_', "-")).as_bytes())
+    } else {
+        Encoding::for_label(label.as_bytes())
+    }
+}
+
+/// Returns the content of the .cpg file that declares the encoding
+///
+/// Code pages are used for the windows encodings, as some software only understand them.
+pub fn cpg_of_encoding(encoding: &'static Encoding) -> String {
+    let name = encoding.name();
+    if encoding == UTF_8 {
+        "UTF-8".to_string()
+    } else if let Some(code_page) = name.strip_prefix("windows-") {
+        code_page.to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// The types of field that can be declared in a schema
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FieldType {
+    /// Text, padded with spaces (`C`)
+    Character,
+    /// Number written as text, with a fixed number of decimals (`N`)
+    Numeric,
+    /// Float written as text, with a fixed number of decimals (`F`)
+    Float,
+    /// Boolean written as one character (`L`)
+    Logical,
+    /// Date written as `YYYYMMDD` (`D`)
+    Date,
+    /// 32 bits little endian integer (`I`)
+    Integer,
+    /// 64 bits little endian float (`B`)
+    Double,
+}
+
+impl FieldType {
+    /// Returns the code of the type as written in the field descriptor
+    fn code(self) -> u8 {
+        match self {
+            FieldType::Character => b'C',
+            FieldType::Numeric => b'N',
+            FieldType::Float => b'F',
+            FieldType::Logical => b'L',
+            FieldType::Date => b'D',
+            FieldType::Integer => b'I',
+            FieldType::Double => b'B',
+        }
+    }
+
+    /// Returns the type of field that can store the value
+    pub fn of(value: &FieldValue) -> FieldType {
+        match value {
+            FieldValue::Character(_) => FieldType::Character,
+            FieldValue::Numeric(_) => FieldType::Numeric,
+            FieldValue::Float(_) => FieldType::Float,
+            FieldValue::Logical(_) => FieldType::Logical,
+            FieldValue::Date(_) => FieldType::Date,
+            FieldValue::Integer(_) => FieldType::Integer,
+            FieldValue::Double(_) => FieldType::Double,
+        }
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldType::Character => write!(f, "Character"),
+            FieldType::Numeric => write!(f, "Numeric"),
+            FieldType::Float => write!(f, "Float"),
+            FieldType::Logical => write!(f, "Logical"),
+            FieldType::Date => write!(f, "Date"),
+            FieldType::Integer => write!(f, "Integer"),
+            FieldType::Double => write!(f, "Double"),
+        }
+    }
+}
+
+/// Description of one field (column) of a .dbf file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldInfo {
+    name: String,
+    field_type: FieldType,
+    length: u8,
+    decimal_count: u8,
+}
+
+impl FieldInfo {
+    /// Creates a field storing text of at most `length` bytes
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidFieldLength` if the length is 0
+    pub fn character<S: Into<String>>(name: S, length: u8) -> Result<Self, Error> {
+        let name = name.into();
+        if length == 0 {
+            return Err(Error::InvalidFieldLength(name));
+        }
+        Ok(Self::new(name, FieldType::Character, length, 0))
+    }
+
+    /// Creates a field storing numbers written with `decimal_count` digits
+    /// after the decimal separator, and `length` characters in total.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidFieldLength` if the length is 0 or if there is no room for the decimals
+    pub fn numeric<S: Into<String>>(name: S, length: u8, decimal_count: u8) -> Result<Self, Error> {
+        Self::new_numeric(name.into(), FieldType::Numeric, length, decimal_count)
+    }
+
+    /// Creates a field storing floats written with `decimal_count` digits
+    /// after the decimal separator, and `length` characters in total.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidFieldLength` if the length is 0 or if there is no room for the decimals
+    pub fn float<S: Into<String>>(name: S, length: u8, decimal_count: u8) -> Result<Self, Error> {
+        Self::new_numeric(name.into(), FieldType::Float, length, decimal_count)
+    }
+
+    /// Creates a field storing booleans
+    pub fn logical<S: Into<String>>(name: S) -> Self {
+        Self::new(name.into(), FieldType::Logical, 1, 0)
+    }
+
+    /// Creates a field storing dates
+    pub fn date<S: Into<String>>(name: S) -> Self {
+        Self::new(name.into(), FieldType::Date, 8, 0)
+    }
+
+    /// Creates a field storing 32 bits integers
+    pub fn integer<S: Into<String>>(name: S) -> Self {
+        Self::new(name.into(), FieldType::Integer, 4, 0)
+    }
+
+    /// Creates a field storing 64 bits floats
+    pub fn double<S: Into<String>>(name: S) -> Self {
+        Self::new(name.into(), FieldType::Double, 8, 0)
+    }
+
+    fn new(name: String, field_type: FieldType, length: u8, decimal_count: u8) -> Self {
+        Self {
+            name,
+            field_type,
+            length,
+            decimal_count,
+        }
+    }
+
+    /// Creates a numeric or float field, which must be long enough to store its decimals
+    fn new_numeric(
+        name: String,
+        field_type: FieldType,
+        length: u8,
+        decimal_count: u8,
+    ) -> Result<Self, Error> {
+        if length == 0 || (decimal_count > 0 && decimal_count >= length - 1) {
+            return Err(Error::InvalidFieldLength(name));
+        }
+        Ok(Self::new(name, field_type, length, decimal_count))
+    }
+
+    /// Returns the name of the field
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the type of the field
+    #[inline]
+    pub fn field_type(&self) -> FieldType {
+        self.field_type
+    }
+
+    /// Returns the number of bytes a value takes in a record
+    #[inline]
+    pub fn length(&self) -> u8 {
+        self.length
+    }
+
+    /// Returns the number of decimals of numeric fields
+    #[inline]
+    pub fn decimal_count(&self) -> u8 {
+        self.decimal_count
+    }
+
+    fn write_to<T: Write>(&self, dest: &mut T) -> Result<(), std::io::Error> {
+        let mut name = [0u8; FIELD_NAME_MAX_LEN + 1];
+        name[..self.name.len()].copy_from_slice(self.name.as_bytes());
+        dest.write_all(&name)?;
+        dest.write_u8(self.field_type.code())?;
+        dest.write_all(&[0u8; 4])?; // displacement
+        dest.write_u8(self.length)?;
+        dest.write_u8(self.decimal_count)?;
+        dest.write_all(&[0u8; 14])?; // flags, autoincrement & reserved
+        Ok(())
+    }
+
+    /// Writes the value, padded to the field length
+    fn write_value<T: Write>(
+        &self,
+        value: &FieldValue,
+        encoding: &'static Encoding,
+        dest: &mut T,
+    ) -> Result<(), Error> {
+        let actual = FieldType::of(value);
+        if actual != self.field_type {
+            return Err(Error::MismatchFieldType {
+                name: self.name.clone(),
+                expected: self.field_type,
+                actual,
+            });
+        }
+
+        match value {
+            FieldValue::Integer(i) => dest.write_i32::<LittleEndian>(*i)?,
+            FieldValue::Double(d) => dest.write_f64::<LittleEndian>(*d)?,
+            _ => {
+                let text = self.value_as_text(value);
+                let (text, _, had_errors) = encoding.encode(&text);
+                if had_errors {
+                    return Err(Error::UnencodableFieldValue {
+                        name: self.name.clone(),
+                        encoding: encoding.name(),
+                    });
+                }
+                if text.len() > self.length as usize {
+                    return Err(Error::FieldValueTooLong {
+                        name: self.name.clone(),
+                        max_length: self.length,
+                    });
+                }
+                let padding = vec![b' '; self.length as usize - text.len()];
+                if self.field_type == FieldType::Character {
+                    dest.write_all(&text)?;
+                    dest.write_all(&padding)?;
+                } else {
+                    // Numbers are right aligned
+                    dest.write_all(&padding)?;
+                    dest.write_all(&text)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the text representation of the value, an empty text means no value
+    fn value_as_text(&self, value: &FieldValue) -> String {
+        let decimals = self.decimal_count as usize;
+        match value {
+            FieldValue::Character(Some(s)) => s.clone(),
+            FieldValue::Numeric(Some(n)) => format!("{:.*}", decimals, n),
+            FieldValue::Float(Some(n)) => format!("{:.*}", decimals, n),
+            FieldValue::Logical(Some(true)) => "T".to_string(),
+            FieldValue::Logical(Some(false)) => "F".to_string(),
+            FieldValue::Date(Some(date)) => date.to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Source of the dbase reader that decodes the records,
+/// the bytes of each record are put in it just before being decoded
+#[derive(Clone, Default)]
+struct RecordSlot(Arc<Mutex<Cursor<Vec<u8>>>>);
+
+impl RecordSlot {
+    /// Replaces the content of the slot with the bytes
+    fn fill(&self, bytes: &[u8]) {
+        let mut cursor = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        cursor.get_mut().clear();
+        cursor.get_mut().extend_from_slice(bytes);
+        cursor.set_position(0);
+    }
+}
+
+impl Read for RecordSlot {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .read(buf)
+    }
+}
+
+/// The header of a .dbf file, which decodes the records
+///
+/// The records are decoded by a reader of the dbase crate, created once
+/// from the header, to which the bytes of the records are given one at a time.
+pub(crate) struct TableHeader {
+    /// Size of the header (with the field descriptors) in the file
+    size: usize,
+    num_records: u32,
+    record_size: usize,
+    /// Name and position in the record of the character fields,
+    /// which are decoded again when the encoding is not UTF-8
+    character_fields: Vec<(String, Range<usize>)>,
+    /// Position in the record of the date fields, checked before dbase decodes them
+    date_fields: Vec<Range<usize>>,
+    encoding: &'static Encoding,
+    /// Where the bytes of the record to decode are put
+    slot: RecordSlot,
+    /// Reader of the records put in the slot, its header says there are `u32::MAX` of them
+    decoder: dbase::Reader<RecordSlot>,
+}
+
+impl TableHeader {
+    /// Number of bytes to read to know the size of the header
+    pub(crate) const PREFIX_SIZE: usize = TABLE_HEADER_SIZE;
+
+    /// Returns the size of the header (with the field descriptors) from its first bytes
+    pub(crate) fn size_from_prefix(prefix: &[u8]) -> Result<usize, Error> {
+        let header_size = LittleEndian::read_u16(&prefix[8..10]) as usize;
+        if header_size <= TABLE_HEADER_SIZE {
+            return Err(invalid_data("dbf header is too small"));
+        }
+        Ok(header_size)
+    }
+
+    /// Reads the whole header from the source, which is left at the first record
+    pub(crate) fn read_from<R: Read>(mut source: R) -> Result<Self, Error> {
+        let mut bytes = vec![0u8; Self::PREFIX_SIZE];
+        source.read_exact(&mut bytes)?;
+        bytes.resize(Self::size_from_prefix(&bytes)?, 0);
+        source.read_exact(&mut bytes[Self::PREFIX_SIZE..])?;
+        Self::from_bytes(bytes)
+    }
+
+    /// Parses the whole header
+    pub(crate) fn from_bytes(mut bytes: Vec<u8>) -> Result<Self, Error> {
+        let num_records = LittleEndian::read_u32(&bytes[4..8]);
+        let record_size = LittleEndian::read_u16(&bytes[10..12]) as usize;
+
+        // Like dbase, the number of fields is deduced from the size of the header,
+        // which panics if the terminator is not right after them
+        let num_fields = (bytes.len() - TABLE_HEADER_SIZE - 1) / FIELD_DESCRIPTOR_SIZE;
+        if bytes[TABLE_HEADER_SIZE + num_fields * FIELD_DESCRIPTOR_SIZE] != HEADER_TERMINATOR {
+            return Err(invalid_data("dbf field descriptors are not terminated"));
+        }
+
+        let mut character_fields = Vec::new();
+        let mut date_fields = Vec::new();
+        let mut field_offset = 1; // deletion flag
+        let descriptors = bytes[TABLE_HEADER_SIZE..].chunks_exact(FIELD_DESCRIPTOR_SIZE);
+        for descriptor in descriptors.take(num_fields) {
+            let code = descriptor[11];
+            let range = field_offset..field_offset + descriptor[16] as usize;
+            field_offset = range.end;
+            if code == FieldType::Character.code() {
+                let name = String::from_utf8_lossy(&descriptor[..FIELD_NAME_MAX_LEN + 1])
+                    .trim_matches('\0')
+                    .to_owned();
+                character_fields.push((name, range));
+            } else if code == FieldType::Date.code() {
+                date_fields.push(range);
+            } else if code == b'Y' || code == b'T' {
+                // Currency and DateTime are known to dbase, but it panics when decoding them
+                return Err(dbase::Error::InvalidFieldType(code as char).into());
+            }
+        }
+
+        LittleEndian::write_u32(&mut bytes[4..8], u32::MAX);
+        let slot = RecordSlot::default();
+        slot.fill(&bytes);
+        let decoder = dbase::Reader::new(slot.clone())?;
+
+        Ok(Self {
+            size: bytes.len(),
+            num_records,
+            record_size,
+            character_fields,
+            date_fields,
+            encoding: UTF_8,
+            slot,
+            decoder,
+        })
+    }
+
+    /// Returns the size of the header in the file
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the number of records, as written in the header
+    pub(crate) fn num_records(&self) -> usize {
+        self.num_records as usize
+    }
+
+    /// Returns the size in bytes of a record
+    pub(crate) fn record_size(&self) -> usize {
+        self.record_size
+    }
+
+    /// Sets the encoding used to decode the character fields
+    pub(crate) fn set_encoding(&mut self, encoding: &'static Encoding) {
+        self.encoding = encoding;
+    }
+
+    pub(crate) fn encoding(&self) -> &'static Encoding {
+        self.encoding
+    }
+
+    /// Decodes the bytes of a record
+    pub(crate) fn decode_record(&mut self, record_bytes: &[u8]) -> Result<Record, Error> {
+        // dbase panics on dates that are not at least 8 ascii characters
+        for range in &self.date_fields {
+            if let Some(bytes) = record_bytes.get(range.clone()) {
+                let is_blank = bytes.iter().all(|&b| b == b' ');
+                if !is_blank && (bytes.len() < 8 || !bytes.is_ascii()) {
+                    return Err(dbase::Error::InvalidDate.into());
+                }
+            }
+        }
+
+        self.slot.fill(record_bytes);
+        let mut record = match self.decoder.next() {
+            Some(record) => record?,
+            None => return Err(invalid_data("too many dbf records were decoded")),
+        };
+
+        // dbase decodes the text as UTF-8
+        if self.encoding != UTF_8 {
+            for (name, range) in &self.character_fields {
+                let bytes = match record_bytes.get(range.clone()) {
+                    Some(bytes) => bytes,
+                    None => continue,
+                };
+                let (text, _) = self.encoding.decode_without_bom_handling(bytes);
+                let text = text.trim();
+                let value = if text.is_empty() {
+                    None
+                } else {
+                    Some(text.to_owned())
+                };
+                record.insert(name.clone(), FieldValue::Character(value));
+            }
+        }
+        Ok(record)
+    }
+}
+
+fn invalid_data(msg: &str) -> Error {
+    Error::IoError(std::io::Error::new(std::io::ErrorKind::InvalidData, msg))
+}
+
+/// struct that reads the records of a .dbf file
+///
+/// Unlike the `Reader` of the dbase crate, this struct keeps the offset and size
+/// of the records so it can seek to any of them.
+pub(crate) struct TableReader<T: Read> {
+    source: T,
+    header: TableHeader,
+    current_record: u32,
+}
+
+impl<T: Read> TableReader<T> {
+    /// Creates a new reader, the header and field descriptors are read upon creation
+    pub(crate) fn new(source: T) -> Result<Self, Error> {
+        Self::read_header(source).map_err(|e| e.with_context(FileRole::Dbf, None, Some(0)))
+    }
+
+    fn read_header(mut source: T) -> Result<Self, Error> {
+        let header = TableHeader::read_from(&mut source)?;
+        Ok(Self {
+            source,
+            header,
+            current_record: 0,
+        })
+    }
+
+    /// Sets the encoding used to decode the character fields
+    pub(crate) fn set_encoding(&mut self, encoding: &'static Encoding) {
+        self.header.set_encoding(encoding);
+    }
+
+    pub(crate) fn encoding(&self) -> &'static Encoding {
+        self.header.encoding()
+    }
+
+    /// Returns the number of records, as written in the header
+    pub(crate) fn num_records(&self) -> usize {
+        self.header.num_records()
+    }
+
+    /// Returns the position of the `index`th record in the file
+    fn position_of(&self, index: usize) -> u64 {
+        (self.header.size() + index * self.header.record_size()) as u64
+    }
+
+    /// Reads the record at the current position of the source,
+    /// `index` is the index of this record
+    fn read_record(&mut self, index: usize) -> Result<Record, Error> {
+        let position = self.position_of(index);
+        let mut record_bytes = vec![0u8; self.header.record_size()];
+        let record = match self.source.read_exact(&mut record_bytes) {
+            Ok(()) => self.header.decode_record(&record_bytes),
+            Err(e) => Err(Error::IoError(e)),
+        };
+        record.map_err(|e| e.with_context(FileRole::Dbf, Some(index), Some(position)))
+    }
+
+    /// Reads all the remaining records
+    pub(crate) fn read(self) -> Result<Vec<Record>, Error> {
+        self.collect()
+    }
+}
+
+impl<T: Read> Iterator for TableReader<T> {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.current_record as usize >= self.num_records() {
+            None
+        } else {
+            self.current_record += 1;
+            Some(self.read_record(self.current_record as usize - 1))
+        }
+    }
+}
+
+impl<T: Read + Seek> TableReader<T> {
+    /// Reads the `n`th record (starting from 0),
+    /// `None` if the index is out of range
+    ///
+    /// The source is then moved back to where it was, so that
+    /// the records can still be iterated over.
+    pub(crate) fn read_nth_record(&mut self, index: usize) -> Option<Result<Record, Error>> {
+        if index >= self.num_records() {
+            return None;
+        }
+        let (record_pos, current_pos) = (
+            self.position_of(index),
+            self.position_of(self.current_record as usize),
+        );
+
+        if let Err(e) = self.source.seek(SeekFrom::Start(record_pos)) {
+            return Some(Err(Error::IoError(e)));
+        }
+        let record = self.read_record(index);
+        if let Err(e) = self.source.seek(SeekFrom::Start(current_pos)) {
+            return Some(Err(Error::IoError(e)));
+        }
+        Some(record)
+    }
+}
+
+/// struct that writes the records of a .dbf file one at a time
+///
+/// Like the [ShapeWriter](../writer/struct.ShapeWriter.html), the number of records
+/// in the header is only correct once the writer is finalized, which happens
+/// when [finalize](#method.finalize) is called or when the writer is dropped.
+pub struct TableWriter<T: Write + Seek> {
+    dest: T,
+    fields: Vec<FieldInfo>,
+    encoding: &'static Encoding,
+    header_pos: u64,
+    num_records: u32,
+    finalized: bool,
+}
+
+impl<T: Write + Seek> TableWriter<T> {
+    /// Creates a new writer and writes the header describing the fields
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidFieldName` if a field name is empty, not ascii, longer than
+    /// 10 bytes or if two fields have the same name.
+    ///
+    /// Returns `Error::TableTooLarge` if the fields do not fit in the header
+    /// or in the records of a .dbf.
+    pub fn new(mut dest: T, fields: Vec<FieldInfo>) -> Result<Self, Error> {
+        for (i, field) in fields.iter().enumerate() {
+            let is_valid = !field.name.is_empty()
+                && field.name.len() <= FIELD_NAME_MAX_LEN
+                && field.name.is_ascii()
+                && fields[..i].iter().all(|other| other.name != field.name);
+            if !is_valid {
+                return Err(Error::InvalidFieldName(field.name.clone()));
+            }
+        }
+
+        let header_pos = dest.stream_position()?;
+        let mut writer = Self {
+            dest,
+            fields,
+            encoding: UTF_8,
+            header_pos,
+            num_records: 0,
+            finalized: false,
+        };
+        // Both sizes are written as u16
+        if writer.header_size() > u16::MAX as usize || writer.record_size() > u16::MAX as usize {
+            // Nothing was written, the header must not be written when dropped
+            writer.finalized = true;
+            return Err(Error::TableTooLarge);
+        }
+        writer.write_header()?;
+        for field in &writer.fields {
+            field.write_to(&mut writer.dest)?;
+        }
+        writer.dest.write_u8(HEADER_TERMINATOR)?;
+        Ok(writer)
+    }
+
+    /// Returns the fields of the table
+    pub fn fields(&self) -> &[FieldInfo] {
+        &self.fields
+    }
+
+    /// Sets the encoding of the text of the records written after this call, UTF-8 by default
+    ///
+    /// The length of the character fields is in bytes of the encoded text.
+    /// Use [cpg_of_encoding](fn.cpg_of_encoding.html) to declare the encoding in a .cpg file.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use shapefile::dbase::{FieldValue, Record};
+    /// use shapefile::dbf::{FieldInfo, TableWriter};
+    /// use shapefile::encoding_rs::WINDOWS_1252;
+    ///
+    /// let fields = vec![FieldInfo::character("NAME", 4).unwrap()];
+    /// let mut writer = TableWriter::new(Cursor::new(Vec::<u8>::new()), fields).unwrap();
+    /// writer.set_encoding(WINDOWS_1252);
+    ///
+    /// let mut record = Record::new();
+    /// record.insert("NAME".to_string(), FieldValue::Character(Some("Sète".to_string())));
+    /// writer.write_record(&record).unwrap();
+    ///
+    /// // Cannot be represented in windows-1252
+    /// record.insert("NAME".to_string(), FieldValue::Character(Some("東京".to_string())));
+    /// assert!(writer.write_record(&record).is_err());
+    /// ```
+    pub fn set_encoding(&mut self, encoding: &'static Encoding) {
+        self.encoding = encoding;
+    }
+
+    /// Returns the encoding of the text of the records
+    pub fn encoding(&self) -> &'static Encoding {
+        self.encoding
+    }
+
+    fn header_size(&self) -> usize {
+        TABLE_HEADER_SIZE + self.fields.len() * FIELD_DESCRIPTOR_SIZE + 1
+    }
+
+    fn record_size(&self) -> usize {
+        1 + self
+            .fields
+            .iter()
+            .map(|field| field.length as usize)
+            .sum::<usize>()
+    }
+
+    fn write_header(&mut self) -> Result<(), std::io::Error> {
+        let header_size = self.header_size();
+        let record_size = self.record_size();
+
+        self.dest.write_u8(TABLE_VERSION)?;
+        self.dest.write_all(&LAST_UPDATE)?;
+        self.dest.write_u32::<LittleEndian>(self.num_records)?;
+        self.dest.write_u16::<LittleEndian>(header_size as u16)?;
+        self.dest.write_u16::<LittleEndian>(record_size as u16)?;
+        self.dest.write_all(&[0u8; 20])?; // reserved, flags & language driver
+        Ok(())
+    }
+
+    /// Checks that the record has exactly the fields of the table,
+    /// with values of the correct type and size
+    pub fn check_record(&self, record: &Record) -> Result<(), Error> {
+        let mut sink = std::io::sink();
+        for field in &self.fields {
+            let value = record
+                .get(&field.name)
+                .ok_or_else(|| Error::MissingField(field.name.clone()))?;
+            field.write_value(value, self.encoding, &mut sink)?;
+        }
+        if let Some(name) = record
+            .keys()
+            .find(|name| self.fields.iter().all(|field| &field.name != *name))
+        {
+            return Err(Error::UnexpectedField(name.clone()));
+        }
+        Ok(())
+    }
+
+    /// Writes one record
+    ///
+    /// # Errors
+    ///
+    /// The record is checked before anything is written,
+    /// (see [check_record](#method.check_record)).
+    pub fn write_record(&mut self, record: &Record) -> Result<(), Error> {
+        let index = self.num_records as usize;
+        let offset = self.header_pos + (self.header_size() + index * self.record_size()) as u64;
+        self.encode_record(record)
+            .map_err(|e| e.with_context(FileRole::Dbf, Some(index), Some(offset)))?;
+        self.num_records += 1;
+        Ok(())
+    }
+
+    fn encode_record(&mut self, record: &Record) -> Result<(), Error> {
+        self.check_record(record)?;
+        self.dest.write_u8(RECORD_NOT_DELETED)?;
+        for field in &self.fields {
+            field.write_value(&record[&field.name], self.encoding, &mut self.dest)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the final header and the file terminator
+    pub fn finalize(mut self) -> Result<(), Error> {
+        self.finalize_file()
+    }
+
+    fn finalize_file(&mut self) -> Result<(), Error> {
+        if self.finalized {
+            return Ok(());
+        }
+        self.finalized = true;
+
+        self.dest.write_u8(FILE_TERMINATOR)?;
+        let end_pos = self.dest.stream_position()?;
+        self.dest.seek(SeekFrom::Start(self.header_pos))?;
+        self.write_header()?;
+        self.dest.seek(SeekFrom::Start(end_pos))?;
+        self.dest.flush()?;
+        Ok(())
+    }
+}
+
+impl<T: Write + Seek> Drop for TableWriter<T> {
+    fn drop(&mut self) {
+        let _ = self.finalize_file();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn invalid_field_names() {
+        let too_long = vec![FieldInfo::logical("NAME_TOO_LONG")];
+        let duplicated = vec![FieldInfo::logical("NAME"), FieldInfo::date("NAME")];
+        for fields in [too_long, duplicated] {
+            let result = TableWriter::new(Cursor::new(Vec::<u8>::new()), fields);
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn invalid_field_lengths() {
+        assert!(FieldInfo::character("NAME", 0).is_err());
+        assert!(FieldInfo::numeric("VALUE", 0, 0).is_err());
+        assert!(FieldInfo::float("VALUE", 3, 2).is_err());
+        assert!(FieldInfo::numeric("VALUE", 4, 2).is_ok());
+
+        // The header would be larger than 65535 bytes
+        let fields = (0..2100)
+            .map(|i| FieldInfo::logical(format!("F{}", i)))
+            .collect::<Vec<_>>();
+        let mut dest = Cursor::new(Vec::<u8>::new());
+        match TableWriter::new(&mut dest, fields) {
+            Err(Error::TableTooLarge) => {}
+            _ => panic!("The fields should not fit in the header"),
+        }
+        assert!(dest.into_inner().is_empty());
+
+        // The records would be larger than 65535 bytes
+        let fields = (0..300)
+            .map(|i| FieldInfo::character(format!("F{}", i), 254).unwrap())
+            .collect::<Vec<_>>();
+        assert!(TableWriter::new(Cursor::new(Vec::<u8>::new()), fields).is_err());
+    }
+
+    #[test]
+    fn header_is_patched() {
+        let mut dest = Cursor::new(Vec::<u8>::new());
+        {
+            let fields = vec![FieldInfo::numeric("VALUE", 5, 1).unwrap()];
+            let mut writer = TableWriter::new(&mut dest, fields).unwrap();
+            let mut record = Record::new();
+            record.insert("VALUE".to_string(), FieldValue::Numeric(Some(4.5)));
+            writer.write_record(&record).unwrap();
+            writer.write_record(&record).unwrap();
+        }
+        let bytes = dest.into_inner();
+        assert_eq!(&bytes[4..8], &[2, 0, 0, 0]);
+        assert_eq!(&bytes[65..], b"   4.5   4.5\x1A");
+    }
+
+    #[test]
+    fn value_too_long() {
+        let fields = vec![FieldInfo::character("NAME", 3).unwrap()];
+        let writer = TableWriter::new(Cursor::new(Vec::<u8>::new()), fields).unwrap();
+        let mut record = Record::new();
+        record.insert(
+            "NAME".to_string(),
+            FieldValue::Character(Some("Lyon".to_string())),
+        );
+        assert!(writer.check_record(&record).is_err());
+    }
+
+    #[test]
+    fn records_are_decoded_with_the_encoding() {
+        use encoding_rs::WINDOWS_1252;
+
+        let mut dest = Cursor::new(Vec::<u8>::new());
+        {
+            let fields = vec![
+                FieldInfo::character("NAME", 8).unwrap(),
+                FieldInfo::numeric("RANK", 3, 0).unwrap(),
+            ];
+            let mut writer = TableWriter::new(&mut dest, fields).unwrap();
+            writer.set_encoding(WINDOWS_1252);
+            let mut record = Record::new();
+            record.insert(
+                "NAME".to_string(),
+                FieldValue::Character(Some("Orléans".to_string())),
+            );
+            record.insert("RANK".to_string(), FieldValue::Numeric(Some(21.0)));
+            writer.write_record(&record).unwrap();
+        }
+        let bytes = dest.into_inner();
+
+        let mut reader = TableReader::new(Cursor::new(&bytes)).unwrap();
+        let record = reader.next().unwrap().unwrap();
+        assert_ne!(
+            record["NAME"],
+            FieldValue::Character(Some("Orléans".to_string()))
+        );
+
+        let mut reader = TableReader::new(Cursor::new(&bytes)).unwrap();
+        reader.set_encoding(WINDOWS_1252);
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!(
+            record["NAME"],
+            FieldValue::Character(Some("Orléans".to_string()))
+        );
+        assert_eq!(record["RANK"], FieldValue::Numeric(Some(21.0)));
+    }
+
+    #[test]
+    fn records_are_read_back() {
+        let fields = vec![
+            FieldInfo::character("NAME", 8).unwrap(),
+            FieldInfo::numeric("RANK", 3, 0).unwrap(),
+            FieldInfo::float("RATIO", 6, 2).unwrap(),
+            FieldInfo::logical("CAPITAL"),
+            FieldInfo::date("SINCE"),
+            FieldInfo::integer("ID"),
+            FieldInfo::double("AREA"),
+        ];
+        let mut first = Record::new();
+        first.insert("NAME".to_string(), FieldValue::Character(None));
+        first.insert("RANK".to_string(), FieldValue::Numeric(None));
+        first.insert("RATIO".to_string(), FieldValue::Float(None));
+        first.insert("CAPITAL".to_string(), FieldValue::Logical(None));
+        first.insert("SINCE".to_string(), FieldValue::Date(None));
+        first.insert("ID".to_string(), FieldValue::Integer(-1));
+        first.insert("AREA".to_string(), FieldValue::Double(0.0));
+        let mut second = Record::new();
+        second.insert(
+            "NAME".to_string(),
+            FieldValue::Character(Some("Nantes".to_string())),
+        );
+        second.insert("RANK".to_string(), FieldValue::Numeric(Some(6.0)));
+        second.insert("RATIO".to_string(), FieldValue::Float(Some(0.25)));
+        second.insert("CAPITAL".to_string(), FieldValue::Logical(Some(true)));
+        second.insert(
+            "SINCE".to_string(),
+            FieldValue::Date(Some("19820101".parse().unwrap())),
+        );
+        second.insert("ID".to_string(), FieldValue::Integer(44));
+        second.insert("AREA".to_string(), FieldValue::Double(65.19));
+
+        let mut dest = Cursor::new(Vec::<u8>::new());
+        {
+            let mut writer = TableWriter::new(&mut dest, fields).unwrap();
+            writer.write_record(&first).unwrap();
+            writer.write_record(&second).unwrap();
+        }
+        let bytes = dest.into_inner();
+
+        let reader = TableReader::new(Cursor::new(&bytes)).unwrap();
+        let records = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(records, vec![first, second]);
+
+        // A value that cannot be parsed is an error, not a panic
+        let mut corrupted = bytes.clone();
+        let date_start = TableReader::new(Cursor::new(&bytes)).unwrap().header.size() + 19;
+        corrupted[date_start..date_start + 8].copy_from_slice(b"2020-1-1");
+        let reader = TableReader::new(Cursor::new(&corrupted)).unwrap();
+        assert!(reader.collect::<Result<Vec<_>, _>>().is_err());
+
+        // dbase panics on these, they are checked before giving them to it
+        let mut corrupted = bytes.clone();
+        corrupted[date_start..date_start + 8].copy_from_slice("202é010".as_bytes());
+        let reader = TableReader::new(Cursor::new(&corrupted)).unwrap();
+        assert!(reader.collect::<Result<Vec<_>, _>>().is_err());
+
+        let mut corrupted = bytes.clone();
+        corrupted[TABLE_HEADER_SIZE + 11] = b'Y';
+        assert!(TableReader::new(Cursor::new(&corrupted)).is_err());
+
+        let mut corrupted = bytes;
+        corrupted[TABLE_HEADER_SIZE + 7 * FIELD_DESCRIPTOR_SIZE] = b' ';
+        assert!(TableReader::new(Cursor::new(&corrupted)).is_err());
+    }
+}
@@ -5,6 +5,9 @@ use record::BBoxZ;
 use std::io::{Read, Write};
 
 pub(crate) const HEADER_SIZE: i32 = 100;
+/// Max size in bytes of a .shp, as its length and the offsets of its records
+/// are `i32` counts of 16-bit words
+pub const MAX_FILE_SIZE: u64 = i32::MAX as u64 * 2;
 const FILE_CODE: i32 = 9994;
 /// Size of reserved bytes in the header, that have do defined use
 const SIZE_OF_SKIP: usize = std::mem::size_of::<i32>() * 5;
@@ -39,6 +42,11 @@ impl Default for Header {
 }
 
 impl Header {
+    /// Returns the length of the file in bytes
+    pub(crate) fn file_size(&self) -> usize {
+        self.file_length.max(0) as usize * 2
+    }
+
     pub fn read_from<T: Read>(mut source: &mut T) -> Result<Header, Error> {
         let file_code = source.read_i32::<BigEndian>()?;
 
@@ -0,0 +1,192 @@
+//! Module to read, write and rebuild the .shx index file
+//!
+//! The .shx gives the position and the size of each record of the .shp,
+//! it is what allows the [Reader](../reader/struct.Reader.html) to read a shape
+//! given its index (see [read_nth_shape](../reader/struct.Reader.html#method.read_nth_shape)).
+//!
+//! When the .shx is missing, or does not match the .shp, it can be rebuilt
+//! from the .shp with [rebuild](fn.rebuild.html).
+//!
+//! # Example
+//!
+//! ```
+//! let shp = std::fs::File::open("tests/data/line.shp").unwrap();
+//! let mut shx = Vec::<u8>::new();
+//! let num_records = shapefile::index::rebuild(shp, &mut shx).unwrap();
+//! assert_eq!(num_records, 1);
+//! assert_eq!(shx, std::fs::read("tests/data/line.shx").unwrap());
+//! ```
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use header;
+use record::RecordHeader;
+use {Error, FileRole};
+
+pub(crate) const INDEX_RECORD_SIZE: usize = 2 * std::mem::size_of::<i32>();
+
+/// Position and size of a record of the .shp, in 16-bit words
+pub(crate) struct ShapeIndex {
+    pub offset: i32,
+    pub record_size: i32,
+}
+
+impl ShapeIndex {
+    /// Returns the position in bytes of the record in the .shp
+    pub(crate) fn offset_in_bytes(&self) -> u64 {
+        self.offset.max(0) as u64 * 2
+    }
+}
+
+/// Read the content of a .shx file
+pub(crate) fn read_index_file<T: Read>(mut source: T) -> Result<Vec<ShapeIndex>, Error> {
+    let header = header::Header::read_from(&mut source)
+        .map_err(|e| e.with_context(FileRole::Shx, None, Some(0)))?;
+
+    if header.file_size() < header::HEADER_SIZE as usize {
+        let error = Error::InvalidFileLength(header.file_length);
+        return Err(error.with_context(FileRole::Shx, None, Some(0)));
+    }
+    let num_shapes = (header.file_size() - header::HEADER_SIZE as usize) / INDEX_RECORD_SIZE;
+    // The number of entries comes from the header, the file may have less
+    let mut shapes_index = Vec::<ShapeIndex>::new();
+    for i in 0..num_shapes {
+        let shape_idx = read_index_record(&mut source).map_err(|e| {
+            let offset = (header::HEADER_SIZE as usize + i * INDEX_RECORD_SIZE) as u64;
+            Error::IoError(e).with_context(FileRole::Shx, Some(i), Some(offset))
+        })?;
+        shapes_index.push(shape_idx);
+    }
+    Ok(shapes_index)
+}
+
+fn read_index_record<T: Read>(source: &mut T) -> Result<ShapeIndex, std::io::Error> {
+    let offset = source.read_i32::<BigEndian>()?;
+    let record_size = source.read_i32::<BigEndian>()?;
+    Ok(ShapeIndex {
+        offset,
+        record_size,
+    })
+}
+
+/// Writes the .shx of the records
+///
+/// Returns `Error::FileTooLarge` if the length of the .shx does not fit in its header.
+pub(crate) fn write_index_file<T: Write>(
+    mut dest: &mut T,
+    shapefile_header: &header::Header,
+    shapes_index: Vec<ShapeIndex>,
+) -> Result<(), Error> {
+    let mut header = *shapefile_header;
+    // In 16-bit words
+    header.file_length = i32::try_from(shapes_index.len() * INDEX_RECORD_SIZE / 2)
+        .ok()
+        .and_then(|content_len| content_len.checked_add(header::HEADER_SIZE / 2))
+        .ok_or(Error::FileTooLarge)?;
+
+    header.write_to(&mut dest)?;
+    for shape_index in shapes_index {
+        dest.write_i32::<BigEndian>(shape_index.offset)?;
+        dest.write_i32::<BigEndian>(shape_index.record_size)?;
+    }
+    Ok(())
+}
+
+/// Walks the records of the .shp, the source must be positioned after the header
+///
+/// The content of the records is skipped, only their headers are read.
+///
+/// # Errors
+///
+/// `Error::InvalidShapeRecordSize` if the content length declared by a record
+/// is too small to hold a shape type, or goes past the end of the file.
+pub(crate) fn index_records<T: Read>(
+    source: &mut T,
+    header: &header::Header,
+) -> Result<Vec<ShapeIndex>, Error> {
+    let file_length = i64::from(header.file_length) * 2;
+    let mut pos = i64::from(header::HEADER_SIZE);
+    let mut shapes_index = Vec::<ShapeIndex>::new();
+    while pos < file_length {
+        let shape_idx = index_record(source, pos, file_length).map_err(|e| {
+            e.with_context(FileRole::Shp, Some(shapes_index.len()), Some(pos as u64))
+        })?;
+        pos += RecordHeader::SIZE as i64 + i64::from(shape_idx.record_size) * 2;
+        shapes_index.push(shape_idx);
+    }
+    Ok(shapes_index)
+}
+
+/// Reads the header of the record at `pos` and skips its content
+fn index_record<T: Read>(source: &mut T, pos: i64, file_length: i64) -> Result<ShapeIndex, Error> {
+    let hdr = RecordHeader::read_from(source)?;
+    let content_length = i64::from(hdr.record_size) * 2;
+    let end = pos + RecordHeader::SIZE as i64 + content_length;
+    if content_length < std::mem::size_of::<i32>() as i64 || end > file_length {
+        return Err(Error::InvalidShapeRecordSize);
+    }
+
+    let skipped = std::io::copy(
+        &mut source.take(content_length as u64),
+        &mut std::io::sink(),
+    )?;
+    if skipped != content_length as u64 {
+        return Err(Error::IoError(std::io::ErrorKind::UnexpectedEof.into()));
+    }
+
+    Ok(ShapeIndex {
+        offset: (pos / 2) as i32,
+        record_size: hdr.record_size,
+    })
+}
+
+/// Rebuilds the .shx of the .shp read from `shp_source`, and writes it to `shx_dest`
+///
+/// The records of the .shp are read one after the other, using the content
+/// length declared in their header, which is checked against the file length.
+///
+/// Returns the number of records in the index.
+///
+/// # Errors
+///
+/// `Error::InvalidShapeRecordSize` if the content length declared by a record
+/// is invalid, in which case nothing is written.
+pub fn rebuild<R: Read, W: Write>(mut shp_source: R, mut shx_dest: W) -> Result<usize, Error> {
+    let header = header::Header::read_from(&mut shp_source)
+        .map_err(|e| e.with_context(FileRole::Shp, None, Some(0)))?;
+    let shapes_index = index_records(&mut shp_source, &header)?;
+    let num_records = shapes_index.len();
+    write_index_file(&mut shx_dest, &header, shapes_index)
+        .and_then(|_| shx_dest.flush().map_err(Error::IoError))
+        .map_err(|e| e.with_context(FileRole::Shx, None, None))?;
+    Ok(num_records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebuild_rejects_invalid_content_length() {
+        let shp = std::fs::read("tests/data/point.shp").unwrap();
+        let mut shx = Vec::<u8>::new();
+        assert_eq!(rebuild(shp.as_slice(), &mut shx).unwrap(), 1);
+        assert_eq!(shx, std::fs::read("tests/data/point.shx").unwrap());
+
+        // The record says it is longer than the file
+        let mut corrupted = shp.clone();
+        corrupted[104..108].copy_from_slice(&100i32.to_be_bytes());
+        let mut shx = Vec::<u8>::new();
+        match rebuild(corrupted.as_slice(), &mut shx) {
+            Err(e) => {
+                assert!(matches!(e.without_context(), Error::InvalidShapeRecordSize));
+                assert_eq!(e.context().unwrap().record_index, Some(0));
+                assert_eq!(e.context().unwrap().offset, Some(100));
+                assert!(shx.is_empty());
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
@@ -24,9 +24,22 @@
 //!
 //! For more details see the [reader](reader/index.html) module
 //!
+//! The coordinate reference system stored in the .prj is described in the
+//! [projection](projection/index.html) module
+//!
+//! The .sbn spatial index written by ESRI software can be read with the [sbn](sbn/index.html) module,
+//! the .qix quadtree index of MapServer and QGIS with the [qix](qix/index.html) module
+//!
+//! A missing or broken .shx can be rebuilt from the .shp with the [index](index/index.html) module
+//!
+//! Files can be checked against the specification with the [validate](validate/index.html) module
+//!
 //! # Writing
 //!
-//! To write a file see the [writer](writer/index.html) module
+//! To write a file see the [writer](writer/index.html) module,
+//! shapes can either be written all at once or one at a time
+//!
+//! A .qix index of the shapes can be built and written with the [qix](qix/index.html) module
 //!
 //! # Features
 //!
@@ -34,6 +47,24 @@
 //! implementations allowing to convert (or try to) back and forth between shapefile's type and
 //! the one in `geo_types`
 //!
+//! The `mmap` feature adds [Reader::from_path_mmap](reader/struct.Reader.html#method.from_path_mmap)
+//! which memory-maps the files instead of reading them through a `BufReader`
+//!
+//! The `rstar` feature adds [Reader::build_rtree](reader/struct.Reader.html#method.build_rtree)
+//! which builds an in-memory R-tree of the bbox of the shapes (see the [rtree](rtree/index.html) module)
+//!
+//! The `zip` feature adds [Reader::from_zip](reader/struct.Reader.html#method.from_zip)
+//! to read shapefiles from zip archives, and the [archive](archive/index.html) module
+//! to list their layers and write archives
+//!
+//! The `async` feature adds the [async_reader](async_reader/index.html) module
+//! to read shapes and records from `tokio::io::AsyncRead` sources as a `Stream`,
+//! and the [async_writer](async_writer/index.html) module to write shapes
+//! to `tokio::io::AsyncWrite + AsyncSeek` destinations
+//!
+//! The `rayon` feature adds [Reader::par_read_as](reader/struct.Reader.html#method.par_read_as)
+//! which uses the .shx to decode the shapes on several threads (see the [parallel](parallel/index.html) module)
+//!
 //! [`Point`]: record/point/struct.Point.html
 //! [`PointM`]: record/point/struct.PointM.html
 //! [`PointZ`]: record/point/struct.PointZ.html
@@ -43,10 +74,27 @@
 //! [`Multipatch`]: record/multipatch/struct.Multipatch.html
 extern crate byteorder;
 pub extern crate dbase;
+pub extern crate encoding_rs;
 
+#[cfg(feature = "zip")]
+pub mod archive;
+#[cfg(feature = "async")]
+pub mod async_reader;
+#[cfg(feature = "async")]
+pub mod async_writer;
+pub mod dbf;
 pub mod header;
+pub mod index;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+pub mod projection;
+pub mod qix;
 pub mod reader;
 pub mod record;
+#[cfg(feature = "rstar")]
+pub mod rtree;
+pub mod sbn;
+pub mod validate;
 pub mod writer;
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
@@ -54,7 +102,7 @@ use std::convert::From;
 use std::fmt;
 use std::io::{Read, Write};
 
-pub use reader::{read, read_as, Reader};
+pub use reader::{read, read_as, Reader, RecordInfo, SkippedRecord};
 pub use record::Multipatch;
 pub use record::{convert_shapes_to_vec_of, HasShapeType, ReadableShape};
 pub use record::{Multipoint, MultipointM, MultipointZ};
@@ -62,11 +110,23 @@ pub use record::{Patch, Shape, NO_DATA};
 pub use record::{Point, PointM, PointZ};
 pub use record::{Polygon, PolygonM, PolygonRing, PolygonZ};
 pub use record::{Polyline, PolylineM, PolylineZ};
-pub use writer::Writer;
+pub use writer::{FeatureWriter, ShapeWriter, SplittingWriter, Writer};
 
 extern crate core;
+#[cfg(feature = "async")]
+extern crate futures_core;
 #[cfg(feature = "geo-types")]
 extern crate geo_types;
+#[cfg(feature = "mmap")]
+extern crate memmap2;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "rstar")]
+pub extern crate rstar;
+#[cfg(feature = "async")]
+extern crate tokio;
+#[cfg(feature = "zip")]
+extern crate zip;
 
 /// All Errors that can happen when using this library
 #[derive(Debug)]
@@ -88,10 +148,181 @@ pub enum Error {
         /// The actual type of the shape
         actual: ShapeType,
     },
+    /// The size of a record does not match the size of its shape,
+    /// or the content length of the record goes past the end of the file
     InvalidShapeRecordSize,
+    /// Error of the dbase crate, that reads the records of the .dbf
     DbaseError(dbase::Error),
+    /// The records were requested but no .dbf was given
     MissingDbf,
+    /// The index of the shapes is needed but no .shx was given
     MissingIndexFile,
+    /// The name of a dbf field is empty, too long, not ascii or used by more than one field
+    InvalidFieldName(String),
+    /// The length of a dbf field is 0, or too short to store its decimals
+    InvalidFieldLength(String),
+    /// The fields of a dbf do not fit in its header or in its records,
+    /// which are at most 65535 bytes
+    TableTooLarge,
+    /// The record written does not have a value for the field
+    MissingField(String),
+    /// The record written has a value for a field that is not declared
+    UnexpectedField(String),
+    /// The value of a record does not have the type declared for the field
+    MismatchFieldType {
+        /// Name of the field
+        name: String,
+        /// The declared type
+        expected: dbf::FieldType,
+        /// The type of the value
+        actual: dbf::FieldType,
+    },
+    /// The value of a record does not fit in the length declared for the field
+    FieldValueTooLong {
+        /// Name of the field
+        name: String,
+        /// The declared length
+        max_length: u8,
+    },
+    /// The .sbn or .qix spatial index is invalid
+    InvalidSpatialIndex(String),
+    /// The WKT of the .prj is invalid
+    InvalidProjection(String),
+    /// The text of a record cannot be represented in the encoding of the .dbf
+    UnencodableFieldValue {
+        /// Name of the field
+        name: String,
+        /// Name of the encoding
+        encoding: &'static str,
+    },
+    /// The number of shapes and the number of records to write are not the same
+    MismatchRecordCount {
+        /// Number of shapes
+        shapes: usize,
+        /// Number of records
+        records: usize,
+    },
+    /// The requested layer is not in the archive, or no layer was requested
+    /// but the archive does not hold exactly one
+    LayerNotFound {
+        /// Name of the requested layer
+        requested: Option<String>,
+        /// Names of the layers of the archive
+        available: Vec<String>,
+    },
+    /// The shapes written do not fit in a .shp,
+    /// which is at most [MAX_FILE_SIZE](header/constant.MAX_FILE_SIZE.html) bytes
+    FileTooLarge,
+    /// The length (in 16-bit words) declared in the header of the file
+    /// is smaller than the header itself
+    InvalidFileLength(i32),
+    /// An error that happened while reading or writing a file,
+    /// with the file and the record where it happened
+    ///
+    /// Its message only tells where the error happened, the error itself is its `source`,
+    /// use [without_context](#method.without_context) to get it as an `Error`.
+    WithContext {
+        /// Where the error happened
+        context: ErrorContext,
+        /// The error
+        error: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Returns the error without the context of where it happened
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use shapefile::{Error, PointM, ShapeType};
+    /// let error = shapefile::read_as::<_, PointM>("tests/data/point.shp").unwrap_err();
+    /// match error.without_context() {
+    ///     Error::MismatchShapeType { actual, .. } => assert_eq!(*actual, ShapeType::Point),
+    ///     _ => panic!("unexpected error: {}", error),
+    /// }
+    /// ```
+    pub fn without_context(&self) -> &Error {
+        match self {
+            Error::WithContext { error, .. } => error.without_context(),
+            error => error,
+        }
+    }
+
+    /// Returns where the error happened, if known
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Adds the context of where the error happened,
+    /// errors that already have a context keep it
+    pub(crate) fn with_context(
+        self,
+        file: FileRole,
+        record_index: Option<usize>,
+        offset: Option<u64>,
+    ) -> Error {
+        match self {
+            Error::WithContext { .. } => self,
+            error => Error::WithContext {
+                context: ErrorContext {
+                    file,
+                    record_index,
+                    offset,
+                },
+                error: Box::new(error),
+            },
+        }
+    }
+}
+
+/// The files of a shapefile
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FileRole {
+    /// The .shp, with the shapes
+    Shp,
+    /// The .shx, with the index of the shapes
+    Shx,
+    /// The .dbf, with the records
+    Dbf,
+}
+
+impl fmt::Display for FileRole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FileRole::Shp => write!(f, ".shp"),
+            FileRole::Shx => write!(f, ".shx"),
+            FileRole::Dbf => write!(f, ".dbf"),
+        }
+    }
+}
+
+/// Where an error happened
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// The file read or written
+    pub file: FileRole,
+    /// Index of the record (starting from 0), `None` if the error is not in a record
+    /// (e.g. in the header)
+    pub record_index: Option<usize>,
+    /// Position in bytes in the file of the record, or of the header, when known
+    pub offset: Option<u64>,
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(index) = self.record_index {
+            write!(f, " record {}", index)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " at byte {}", offset)?;
+        }
+        Ok(())
+    }
 }
 
 impl From<std::io::Error> for Error {
@@ -109,7 +340,7 @@ impl From<dbase::Error> for Error {
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Error::IoError(e) => write!(f, "{}", e),
+            Error::IoError(_) => write!(f, "I/O error"),
             Error::InvalidFileCode(code) => write!(
                 f,
                 "The file code ' {} ' is invalid, is this a Shapefile ?",
@@ -120,17 +351,125 @@ impl fmt::Display for Error {
                 "The code ' {} ' does not correspond to any of the ShapeType code defined by ESRI",
                 code
             ),
+            Error::InvalidPatchType(code) => write!(
+                f,
+                "The code ' {} ' does not correspond to any of the PatchType code defined by ESRI",
+                code
+            ),
+            Error::InvalidShapeRecordSize => write!(
+                f,
+                "The size of the record does not match the size of its shape"
+            ),
+            Error::DbaseError(e) => match e {
+                dbase::Error::IoError(_) => write!(f, "I/O error"),
+                dbase::Error::ParseFloatError(_) => write!(f, "Invalid float value"),
+                dbase::Error::ParseIntError(_) => write!(f, "Invalid integer value"),
+                dbase::Error::InvalidFieldType(c) => write!(f, "The field type '{}' is invalid", c),
+                dbase::Error::InvalidDate => write!(f, "Invalid date value"),
+                dbase::Error::FieldLengthTooLong => write!(f, "The field length is too long"),
+                dbase::Error::FieldNameTooLong => write!(f, "The field name is too long"),
+            },
+            Error::MissingDbf => write!(f, "The records were requested but no .dbf was given"),
+            Error::MissingIndexFile => write!(
+                f,
+                "The index of the shapes was requested but no .shx was given"
+            ),
             Error::MismatchShapeType { requested, actual } => write!(
                 f,
                 "The requested type: '{}' does not correspond to the actual shape type: '{}'",
                 requested, actual
             ),
-            e => write!(f, "{:?}", e),
+            Error::InvalidFieldName(name) => write!(f, "The field name '{}' is invalid", name),
+            Error::InvalidFieldLength(name) => write!(
+                f,
+                "The length of the field '{}' is 0 or too short for its decimals",
+                name
+            ),
+            Error::TableTooLarge => write!(
+                f,
+                "The fields do not fit in a .dbf, whose header and records are at most {} bytes",
+                u16::MAX
+            ),
+            Error::MissingField(name) => write!(f, "The record has no value for field '{}'", name),
+            Error::UnexpectedField(name) => {
+                write!(
+                    f,
+                    "The record has a value for the undeclared field '{}'",
+                    name
+                )
+            }
+            Error::MismatchFieldType {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "The field '{}' is of type '{}' but the record value is of type '{}'",
+                name, expected, actual
+            ),
+            Error::FieldValueTooLong { name, max_length } => write!(
+                f,
+                "The record value for field '{}' does not fit in {} bytes",
+                name, max_length
+            ),
+            Error::InvalidSpatialIndex(msg) => write!(f, "Invalid spatial index: {}", msg),
+            Error::InvalidProjection(msg) => write!(f, "Invalid projection: {}", msg),
+            Error::UnencodableFieldValue { name, encoding } => write!(
+                f,
+                "The record value for field '{}' cannot be encoded in {}",
+                name, encoding
+            ),
+            Error::MismatchRecordCount { shapes, records } => write!(
+                f,
+                "There are {} shapes but {} records to write",
+                shapes, records
+            ),
+            Error::LayerNotFound {
+                requested: Some(name),
+                available,
+            } => write!(
+                f,
+                "The archive has no layer '{}', its layers are: {}",
+                name,
+                available.join(", ")
+            ),
+            Error::LayerNotFound {
+                requested: None,
+                available,
+            } => write!(
+                f,
+                "A layer must be chosen among the layers of the archive: {}",
+                available.join(", ")
+            ),
+            Error::FileTooLarge => write!(
+                f,
+                "The shapes do not fit in a .shp, which is at most {} bytes",
+                header::MAX_FILE_SIZE
+            ),
+            Error::InvalidFileLength(length) => write!(
+                f,
+                "The file length {} (in 16-bit words) is smaller than the header",
+                length
+            ),
+            Error::WithContext { context, .. } => write!(f, "Error in {}", context),
         }
     }
 }
 
-impl std::error::Error for Error {}
+/// The wrapped errors are the source of the error,
+/// their message is not repeated in the message of the error
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::IoError(e) => Some(e),
+            Error::DbaseError(dbase::Error::IoError(e)) => Some(e),
+            Error::DbaseError(dbase::Error::ParseFloatError(e)) => Some(e),
+            Error::DbaseError(dbase::Error::ParseIntError(e)) => Some(e),
+            Error::WithContext { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 /// The enum for the ShapeType as defined in the
 /// specification
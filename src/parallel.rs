@@ -0,0 +1,211 @@
+//! Module to decode the shapes of a shapefile on several threads
+//!
+//! Requires the `rayon` feature.
+//!
+//! The position of every record is known from the *.shx* (or from the index built
+//! when there is none), the records are split in chunks that are read (with positional reads, see [ReadAt](trait.ReadAt.html))
+//! and decoded on the threads of rayon's pool, the shapes are still returned in the order of the file.
+//!
+//! # Example
+//!
+//! ```
+//! use shapefile::Polyline;
+//!
+//! let mut reader = shapefile::Reader::from_path("tests/data/line.shp").unwrap();
+//! let polylines = reader.par_read_as::<Polyline>().unwrap();
+//! assert_eq!(polylines, shapefile::read_as::<_, Polyline>("tests/data/line.shp").unwrap());
+//! ```
+use std::io::{self, BufReader, Cursor};
+
+use header;
+use index::ShapeIndex;
+use record::{ReadableShape, RecordHeader};
+use {Error, FileRole};
+
+/// Number of records decoded by each task
+pub(crate) const RECORDS_PER_CHUNK: usize = 1024;
+/// Max number of bytes read at once, the positions and sizes of the records
+/// come from the *.shx* and cannot be trusted to allocate more
+const MAX_READ_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Sources that can be read at any position, from several threads at once
+///
+/// Reading at a position does not change the position of the source used
+/// by the other methods of the [Reader](../reader/struct.Reader.html).
+pub trait ReadAt: Sync {
+    /// Reads exactly `buf.len()` bytes starting at `offset`
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()>;
+}
+
+impl ReadAt for [u8] {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        let start = offset as usize;
+        match start.checked_add(buf.len()) {
+            Some(end) if offset <= usize::MAX as u64 && end <= self.len() => {
+                buf.copy_from_slice(&self[start..end]);
+                Ok(())
+            }
+            _ => Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+}
+
+/// In-memory sources, including the memory-mapped files of the `mmap` feature
+impl<B: AsRef<[u8]> + Sync> ReadAt for Cursor<B> {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        self.get_ref().as_ref().read_exact_at(buf, offset)
+    }
+}
+
+/// On unix, positional reads do not move the cursor of the file
+#[cfg(unix)]
+impl ReadAt for std::fs::File {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        std::os::unix::fs::FileExt::read_exact_at(self, buf, offset)
+    }
+}
+
+/// On windows, positional reads move the cursor of the file: the reads are done
+/// one at a time, and the cursor is moved back to where it was after each of them
+#[cfg(windows)]
+impl ReadAt for std::fs::File {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        use std::io::{Seek, SeekFrom};
+        use std::sync::{Mutex, PoisonError};
+
+        static CURSOR_LOCK: Mutex<()> = Mutex::new(());
+        let _lock = CURSOR_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut file = self;
+        let position = file.stream_position()?;
+        let result = seek_read_exact(self, buf, offset);
+        file.seek(SeekFrom::Start(position))?;
+        result
+    }
+}
+
+#[cfg(windows)]
+fn seek_read_exact(file: &std::fs::File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        let position = offset + filled as u64;
+        match std::os::windows::fs::FileExt::seek_read(file, &mut buf[filled..], position) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => filled += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// The buffer is bypassed
+impl<R: ReadAt> ReadAt for BufReader<R> {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        self.get_ref().read_exact_at(buf, offset)
+    }
+}
+
+/// Returns the position in bytes of the end of the record of the entry,
+/// if the entry is valid for a file of `file_size` bytes
+fn end_of_record(shape_idx: &ShapeIndex, file_size: u64) -> Option<u64> {
+    if shape_idx.offset < header::HEADER_SIZE / 2 || shape_idx.record_size < 2 {
+        return None;
+    }
+    let end =
+        (shape_idx.offset as u64 + shape_idx.record_size as u64) * 2 + RecordHeader::SIZE as u64;
+    Some(end).filter(|end| *end <= file_size)
+}
+
+/// Reads `len` bytes at `offset`, at most `MAX_READ_SIZE` bytes at a time,
+/// so that the buffer only grows with the bytes that could be read
+fn read_at<T: ReadAt>(source: &T, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    while (bytes.len() as u64) < len {
+        let filled = bytes.len();
+        let step = (len - filled as u64).min(MAX_READ_SIZE);
+        bytes.resize(filled + step as usize, 0);
+        source.read_exact_at(&mut bytes[filled..], offset + filled as u64)?;
+    }
+    Ok(bytes)
+}
+
+/// Reads the records of the chunk and decodes them
+///
+/// The records are read with one read when they are close enough,
+/// one at a time otherwise.
+///
+/// `first_index` is the index of the first record of the chunk,
+/// `file_size` the size in bytes of the .shp, which the records must not go past.
+pub(crate) fn decode_chunk<T: ReadAt, S: ReadableShape>(
+    source: &T,
+    chunk: &[ShapeIndex],
+    first_index: usize,
+    file_size: u64,
+) -> Result<Vec<S>, Error> {
+    // The entries come from the .shx, they are checked before being used to read
+    let mut ends = Vec::<u64>::with_capacity(chunk.len());
+    for (i, shape_idx) in chunk.iter().enumerate() {
+        match end_of_record(shape_idx, file_size) {
+            Some(record_end) => ends.push(record_end),
+            None => {
+                let index = first_index + i;
+                let offset = (header::HEADER_SIZE as usize + index * 8) as u64;
+                let error = Error::InvalidShapeRecordSize;
+                return Err(error.with_context(FileRole::Shx, Some(index), Some(offset)));
+            }
+        }
+    }
+    let end = ends.iter().copied().max().unwrap_or(0);
+    let start = chunk
+        .iter()
+        .map(ShapeIndex::offset_in_bytes)
+        .min()
+        .unwrap_or(end);
+
+    let mut shapes = Vec::<S>::with_capacity(chunk.len());
+    if end - start <= MAX_READ_SIZE {
+        let bytes = read_at(source, start, end - start).map_err(|e| {
+            Error::IoError(e).with_context(FileRole::Shp, Some(first_index), Some(start))
+        })?;
+        for (i, shape_idx) in chunk.iter().enumerate() {
+            let offset = shape_idx.offset_in_bytes();
+            let shape = decode_record(&bytes[(offset - start) as usize..])
+                .map_err(|e| e.with_context(FileRole::Shp, Some(first_index + i), Some(offset)))?;
+            shapes.push(shape);
+        }
+    } else {
+        for (i, (shape_idx, record_end)) in chunk.iter().zip(ends).enumerate() {
+            let offset = shape_idx.offset_in_bytes();
+            let shape = read_at(source, offset, record_end - offset)
+                .map_err(Error::IoError)
+                .and_then(|bytes| decode_record(&bytes))
+                .map_err(|e| e.with_context(FileRole::Shp, Some(first_index + i), Some(offset)))?;
+            shapes.push(shape);
+        }
+    }
+    Ok(shapes)
+}
+
+fn decode_record<S: ReadableShape>(mut record: &[u8]) -> Result<S, Error> {
+    let hdr = RecordHeader::read_from(&mut record)?;
+    let record_size = hdr.content_length()?;
+    if record_size > record.len() {
+        return Err(Error::IoError(io::ErrorKind::UnexpectedEof.into()));
+    }
+    S::read_from(&mut record, record_size as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_exact_at_slice() {
+        let bytes = [0u8, 1, 2, 3, 4];
+        let mut buf = [0u8; 2];
+        bytes[..].read_exact_at(&mut buf, 3).unwrap();
+        assert_eq!(buf, [3, 4]);
+        assert!(bytes[..].read_exact_at(&mut buf, 4).is_err());
+        assert!(bytes[..].read_exact_at(&mut buf, u64::MAX).is_err());
+    }
+}
@@ -0,0 +1,201 @@
+//! Module to read and write the .prj file that stores the coordinate reference system
+//! of a shapefile
+//!
+//! The .prj contains the description of the coordinate reference system
+//! as [WKT](https://en.wikipedia.org/wiki/Well-known_text_representation_of_coordinate_reference_systems),
+//! only its root (type and name of the coordinate system) is parsed,
+//! the raw WKT is kept as is so that it can be given to a projection library.
+//!
+//! # Example
+//!
+//! ```
+//! use shapefile::projection::{CoordinateSystemType, Projection};
+//!
+//! let wkt = r#"GEOGCS["GCS_WGS_1984",DATUM["D_WGS_1984",SPHEROID["WGS_1984",6378137.0,298.257223563]],PRIMEM["Greenwich",0.0],UNIT["Degree",0.0174532925199433]]"#;
+//! let projection = Projection::from_wkt(wkt).unwrap();
+//! assert_eq!(projection.name(), "GCS_WGS_1984");
+//! assert_eq!(*projection.cs_type(), CoordinateSystemType::Geographic);
+//! assert_eq!(projection.wkt(), wkt);
+//! ```
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use Error;
+
+fn invalid(msg: &str) -> Error {
+    Error::InvalidProjection(msg.to_string())
+}
+
+/// The type of the root coordinate system of the WKT
+#[derive(Debug, Clone, PartialEq)]
+pub enum CoordinateSystemType {
+    /// `PROJCS` (or `PROJCRS` in WKT2)
+    Projected,
+    /// `GEOGCS` (or `GEOGCRS` in WKT2)
+    Geographic,
+    /// `GEOCCS`
+    Geocentric,
+    /// `VERT_CS` (or `VERTCRS` in WKT2)
+    Vertical,
+    /// `COMPD_CS` (or `COMPOUNDCRS` in WKT2)
+    Compound,
+    /// `LOCAL_CS` (or `ENGCRS` in WKT2)
+    Local,
+    /// Any other keyword
+    Other(String),
+}
+
+impl CoordinateSystemType {
+    fn from_keyword(keyword: &str) -> Self {
+        match keyword.to_ascii_uppercase().as_str() {
+            "PROJCS" | "PROJCRS" | "PROJECTEDCRS" => CoordinateSystemType::Projected,
+            "GEOGCS" | "GEOGCRS" | "GEOGRAPHICCRS" => CoordinateSystemType::Geographic,
+            "GEOCCS" => CoordinateSystemType::Geocentric,
+            "VERT_CS" | "VERTCRS" | "VERTICALCRS" => CoordinateSystemType::Vertical,
+            "COMPD_CS" | "COMPOUNDCRS" => CoordinateSystemType::Compound,
+            "LOCAL_CS" | "ENGCRS" | "ENGINEERINGCRS" => CoordinateSystemType::Local,
+            _ => CoordinateSystemType::Other(keyword.to_string()),
+        }
+    }
+}
+
+/// The coordinate reference system of a shapefile
+#[derive(Debug, Clone, PartialEq)]
+pub struct Projection {
+    wkt: String,
+    name: String,
+    cs_type: CoordinateSystemType,
+}
+
+impl Projection {
+    /// Parses the root of the WKT
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidProjection` if the WKT does not start with a keyword
+    /// followed by the quoted name of the coordinate system
+    pub fn from_wkt<S: Into<String>>(wkt: S) -> Result<Self, Error> {
+        let wkt = wkt.into();
+        let content = wkt.trim_start_matches('\u{feff}').trim();
+
+        let keyword_end = content
+            .find(['[', '('])
+            .ok_or_else(|| invalid("missing opening bracket"))?;
+        let keyword = content[..keyword_end].trim();
+        if keyword.is_empty()
+            || !keyword
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(invalid("invalid keyword"));
+        }
+
+        let rest = content[keyword_end + 1..].trim_start();
+        if !rest.starts_with('"') {
+            return Err(invalid("missing name"));
+        }
+        // Quotes in the name are escaped by doubling them
+        let mut name = String::new();
+        let mut chars = rest[1..].chars().peekable();
+        loop {
+            match chars.next() {
+                Some('"') if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    name.push('"');
+                }
+                Some('"') => break,
+                Some(c) => name.push(c),
+                None => return Err(invalid("unterminated name")),
+            }
+        }
+
+        let cs_type = CoordinateSystemType::from_keyword(keyword);
+        let wkt = content.to_string();
+        Ok(Self { wkt, name, cs_type })
+    }
+
+    /// Reads the whole .prj from the source
+    pub fn read_from<T: Read>(mut source: T) -> Result<Self, Error> {
+        let mut wkt = String::new();
+        source.read_to_string(&mut wkt)?;
+        Self::from_wkt(wkt.trim_end_matches('\0'))
+    }
+
+    /// Reads the .prj file at the given path
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::read_from(File::open(path)?)
+    }
+
+    /// Writes the WKT
+    pub fn write_to<W: Write>(&self, mut dest: W) -> Result<(), Error> {
+        dest.write_all(self.wkt.as_bytes())?;
+        Ok(())
+    }
+
+    /// Writes the WKT to the file at the given path
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        self.write_to(File::create(path)?)
+    }
+
+    /// Returns the WKT (without leading and trailing whitespaces)
+    pub fn wkt(&self) -> &str {
+        &self.wkt
+    }
+
+    /// Returns the name of the root coordinate system
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the type of the root coordinate system
+    pub fn cs_type(&self) -> &CoordinateSystemType {
+        &self.cs_type
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_projected() {
+        let wkt = "PROJCS[\"NAD_1983_UTM_Zone_10N\",GEOGCS[\"GCS_North_American_1983\",\
+                   DATUM[\"D_North_American_1983\",SPHEROID[\"GRS_1980\",6378137.0,298.257222101]],\
+                   PRIMEM[\"Greenwich\",0.0],UNIT[\"Degree\",0.0174532925199433]],\
+                   PROJECTION[\"Transverse_Mercator\"],UNIT[\"Meter\",1.0]]\n";
+        let projection = Projection::from_wkt(wkt).unwrap();
+        assert_eq!(projection.name(), "NAD_1983_UTM_Zone_10N");
+        assert_eq!(*projection.cs_type(), CoordinateSystemType::Projected);
+        assert_eq!(projection.wkt(), wkt.trim_end());
+    }
+
+    #[test]
+    fn parse_escaped_name() {
+        let projection = Projection::from_wkt("LOCAL_CS [ \"My \"\"local\"\" cs\"]").unwrap();
+        assert_eq!(projection.name(), "My \"local\" cs");
+        assert_eq!(*projection.cs_type(), CoordinateSystemType::Local);
+
+        let projection = Projection::from_wkt("BOUNDCRS[\"\"]").unwrap();
+        assert_eq!(
+            *projection.cs_type(),
+            CoordinateSystemType::Other("BOUNDCRS".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid_wkt() {
+        for wkt in &[
+            "",
+            "GEOGCS",
+            "GEOGCS[WGS84]",
+            "GEOGCS[\"WGS84",
+            "GEO GCS[\"WGS84\"]",
+        ] {
+            match Projection::from_wkt(*wkt) {
+                Err(Error::InvalidProjection(_)) => {}
+                other => panic!("Unexpected result for '{}': {:?}", wkt, other),
+            }
+        }
+    }
+}
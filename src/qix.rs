@@ -0,0 +1,436 @@
+//! Module to read and write the .qix quadtree spatial index
+//!
+//! The .qix format is the one written by [shapelib](http://shapelib.maptools.org/) (`shptree.c`)
+//! and used by MapServer, QGIS and GDAL.
+//!
+//! The index is a tree whose root covers the extent of the shapefile,
+//! each node having at most 4 children covering a part of the node.
+//! A shape is stored in the deepest node that fully contains its bbox.
+//!
+//! # Examples
+//!
+//! Querying an existing index:
+//!
+//! ```no_run
+//! use shapefile::record::GenericBBox;
+//! use shapefile::qix::QuadTree;
+//! use shapefile::Point;
+//!
+//! let tree = QuadTree::from_path("states.qix").unwrap();
+//! let bbox = GenericBBox { min: Point::new(-80.0, 35.0), max: Point::new(-75.0, 40.0) };
+//! for shape_index in tree.query(&bbox) {
+//!     println!("The shape n°{} may intersect the bbox", shape_index);
+//! }
+//! ```
+//!
+//! Building the index of the shapes written:
+//!
+//! ```
+//! use shapefile::qix::QuadTree;
+//! use shapefile::Point;
+//!
+//! let points = vec![Point::new(0.0, 0.0), Point::new(1.0, 0.0), Point::new(2.0, 0.0)];
+//! let mut writer = shapefile::Writer::from_path("indexed_points.shp").unwrap();
+//! writer.write_shapes(&points).unwrap();
+//!
+//! let tree = QuadTree::from_shapes(&points);
+//! tree.write_to_path("indexed_points.qix").unwrap();
+//! # std::fs::remove_file("indexed_points.qix").unwrap();
+//! ```
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use record::{EsriShape, GenericBBox};
+use {Error, Point, ShapeType};
+
+/// Size of the header
+const QIX_HEADER_SIZE: usize = 16;
+/// Size of a node without its shapes ids
+const NODE_SIZE: usize = 4 + 4 * 8 + 4 + 4;
+/// Max depth of the tree when it is computed from the number of shapes
+const MAX_DEFAULT_DEPTH: u32 = 12;
+/// Max depth accepted when reading a tree
+const MAX_READ_DEPTH: u32 = 32;
+/// Each child covers this ratio of the longest side of its parent,
+/// so that children overlap a bit
+const SPLIT_RATIO: f64 = 0.55;
+/// Byte order flags of the header
+const LSB_ORDER: u8 = 1;
+const MSB_ORDER: u8 = 2;
+
+fn invalid(msg: &str) -> Error {
+    Error::InvalidSpatialIndex(msg.to_string())
+}
+
+fn bbox_of_shape<S: EsriShape>(shape: &S) -> GenericBBox<Point> {
+    let x_range = shape.x_range();
+    let y_range = shape.y_range();
+    GenericBBox {
+        min: Point::new(x_range[0], y_range[0]),
+        max: Point::new(x_range[1], y_range[1]),
+    }
+}
+
+fn contains(outer: &GenericBBox<Point>, inner: &GenericBBox<Point>) -> bool {
+    outer.min.x <= inner.min.x
+        && inner.max.x <= outer.max.x
+        && outer.min.y <= inner.min.y
+        && inner.max.y <= outer.max.y
+}
+
+/// Splits the bbox in two along its longest side
+fn split(bbox: &GenericBBox<Point>) -> [GenericBBox<Point>; 2] {
+    let mut first = *bbox;
+    let mut second = *bbox;
+    let width = bbox.max.x - bbox.min.x;
+    let height = bbox.max.y - bbox.min.y;
+    if width > height {
+        first.max.x = bbox.min.x + width * SPLIT_RATIO;
+        second.min.x = bbox.max.x - width * SPLIT_RATIO;
+    } else {
+        first.max.y = bbox.min.y + height * SPLIT_RATIO;
+        second.min.y = bbox.max.y - height * SPLIT_RATIO;
+    }
+    [first, second]
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    bbox: GenericBBox<Point>,
+    /// 0-based indices of the shapes
+    shapes: Vec<i32>,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn new(bbox: GenericBBox<Point>) -> Self {
+        Self {
+            bbox,
+            shapes: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.shapes.is_empty() && self.children.is_empty()
+    }
+
+    fn insert(&mut self, id: i32, bbox: &GenericBBox<Point>, max_depth: u32) {
+        if max_depth > 1 {
+            if self.children.is_empty() {
+                let halves = split(&self.bbox);
+                let quarters = [split(&halves[0]), split(&halves[1])];
+                if quarters
+                    .iter()
+                    .flatten()
+                    .any(|quarter| contains(quarter, bbox))
+                {
+                    self.children = quarters.iter().flatten().cloned().map(Node::new).collect();
+                }
+            }
+            if let Some(child) = self.children.iter_mut().find(|c| contains(&c.bbox, bbox)) {
+                return child.insert(id, bbox, max_depth - 1);
+            }
+        }
+        self.shapes.push(id);
+    }
+
+    /// Removes the empty children, and replaces a node without shapes
+    /// by its child if it only has one
+    fn trim(&mut self) {
+        for child in &mut self.children {
+            child.trim();
+        }
+        self.children.retain(|child| !child.is_empty());
+        if self.children.len() == 1 && self.shapes.is_empty() {
+            *self = self.children.pop().unwrap();
+        }
+    }
+
+    /// Size in bytes of the children of this node (recursively)
+    fn children_size(&self) -> usize {
+        self.children
+            .iter()
+            .map(|child| NODE_SIZE + child.shapes.len() * 4 + child.children_size())
+            .sum()
+    }
+
+    fn read_from<T: Read, E: ByteOrder>(source: &mut T, depth: u32) -> Result<Self, Error> {
+        let _children_size = source.read_u32::<E>()?;
+        let mut node = Node::new(GenericBBox::default());
+        node.bbox.min.x = source.read_f64::<E>()?;
+        node.bbox.min.y = source.read_f64::<E>()?;
+        node.bbox.max.x = source.read_f64::<E>()?;
+        node.bbox.max.y = source.read_f64::<E>()?;
+
+        let num_shapes = source.read_i32::<E>()?;
+        if num_shapes < 0 {
+            return Err(invalid("invalid number of shapes in a node"));
+        }
+        for _ in 0..num_shapes {
+            let id = source.read_i32::<E>()?;
+            if id < 0 {
+                return Err(invalid("invalid shape id"));
+            }
+            node.shapes.push(id);
+        }
+
+        let num_children = source.read_i32::<E>()?;
+        if !(0..=4).contains(&num_children) {
+            return Err(invalid("invalid number of children in a node"));
+        }
+        if num_children > 0 && depth == MAX_READ_DEPTH {
+            return Err(invalid("the tree is too deep"));
+        }
+        for _ in 0..num_children {
+            node.children
+                .push(Node::read_from::<T, E>(source, depth + 1)?);
+        }
+        Ok(node)
+    }
+
+    fn write_to<W: Write>(&self, dest: &mut W) -> Result<(), std::io::Error> {
+        dest.write_u32::<LittleEndian>(self.children_size() as u32)?;
+        dest.write_f64::<LittleEndian>(self.bbox.min.x)?;
+        dest.write_f64::<LittleEndian>(self.bbox.min.y)?;
+        dest.write_f64::<LittleEndian>(self.bbox.max.x)?;
+        dest.write_f64::<LittleEndian>(self.bbox.max.y)?;
+        dest.write_i32::<LittleEndian>(self.shapes.len() as i32)?;
+        for id in &self.shapes {
+            dest.write_i32::<LittleEndian>(*id)?;
+        }
+        dest.write_i32::<LittleEndian>(self.children.len() as i32)?;
+        for child in &self.children {
+            child.write_to(dest)?;
+        }
+        Ok(())
+    }
+
+    fn search(&self, bbox: &GenericBBox<Point>, shapes: &mut Vec<usize>) {
+        if !self.bbox.overlaps(bbox) {
+            return;
+        }
+        shapes.extend(self.shapes.iter().map(|id| *id as usize));
+        for child in &self.children {
+            child.search(bbox, shapes);
+        }
+    }
+}
+
+/// A quadtree spatial index, as stored in a .qix file
+#[derive(Debug, Clone)]
+pub struct QuadTree {
+    num_shapes: usize,
+    max_depth: u32,
+    root: Node,
+}
+
+impl QuadTree {
+    /// Builds the index of the shapes
+    ///
+    /// The depth of the tree is chosen so that there are about 8 shapes per node
+    /// (but is not more than 12), like shapelib does.
+    pub fn from_shapes<S: EsriShape>(shapes: &[S]) -> Self {
+        let mut max_depth = 0;
+        let mut max_node_count = 1usize;
+        while max_node_count * 4 < shapes.len() {
+            max_depth += 1;
+            max_node_count *= 2;
+        }
+        Self::from_shapes_with_max_depth(shapes, max_depth.min(MAX_DEFAULT_DEPTH))
+    }
+
+    /// Builds the index of the shapes, with a tree of at most `max_depth` levels
+    pub fn from_shapes_with_max_depth<S: EsriShape>(shapes: &[S], max_depth: u32) -> Self {
+        // Null shapes are not indexed, but keep their id
+        let bboxes = shapes
+            .iter()
+            .map(|shape| {
+                if shape.record_shapetype() == ShapeType::NullShape {
+                    None
+                } else {
+                    Some(bbox_of_shape(shape))
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut extent = bboxes.iter().flatten().next().cloned().unwrap_or_default();
+        for bbox in bboxes.iter().flatten() {
+            extent.min.x = extent.min.x.min(bbox.min.x);
+            extent.min.y = extent.min.y.min(bbox.min.y);
+            extent.max.x = extent.max.x.max(bbox.max.x);
+            extent.max.y = extent.max.y.max(bbox.max.y);
+        }
+
+        let mut root = Node::new(extent);
+        for (id, bbox) in bboxes.iter().enumerate() {
+            if let Some(bbox) = bbox {
+                root.insert(id as i32, bbox, max_depth);
+            }
+        }
+        root.trim();
+        Self {
+            num_shapes: shapes.len(),
+            max_depth,
+            root,
+        }
+    }
+
+    /// Reads the whole .qix from the source
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidSpatialIndex` if the content is not a valid .qix
+    pub fn read_from<T: Read>(mut source: T) -> Result<Self, Error> {
+        let mut header = [0u8; QIX_HEADER_SIZE];
+        source.read_exact(&mut header)?;
+        if &header[..3] != b"SQT" {
+            return Err(invalid("invalid signature"));
+        }
+        // Old versions of the format used the native byte order, (flag set to 0)
+        // we assume it was little endian
+        if header[3] == MSB_ORDER {
+            Self::read_tree::<T, BigEndian>(source, &header)
+        } else {
+            Self::read_tree::<T, LittleEndian>(source, &header)
+        }
+    }
+
+    fn read_tree<T: Read, E: ByteOrder>(mut source: T, header: &[u8]) -> Result<Self, Error> {
+        let num_shapes = E::read_i32(&header[8..12]);
+        let max_depth = E::read_i32(&header[12..16]);
+        if num_shapes < 0 {
+            return Err(invalid("invalid number of shapes"));
+        }
+        if max_depth < 0 {
+            return Err(invalid("invalid max depth"));
+        }
+        let root = Node::read_from::<T, E>(&mut source, 0)?;
+        Ok(Self {
+            num_shapes: num_shapes as usize,
+            max_depth: max_depth as u32,
+            root,
+        })
+    }
+
+    /// Reads the .qix file at the given path
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Writes the index, in little endian
+    pub fn write_to<W: Write>(&self, mut dest: W) -> Result<(), Error> {
+        dest.write_all(b"SQT")?;
+        dest.write_all(&[LSB_ORDER, 1, 0, 0, 0])?;
+        dest.write_i32::<LittleEndian>(self.num_shapes as i32)?;
+        dest.write_i32::<LittleEndian>(self.max_depth as i32)?;
+        self.root.write_to(&mut dest)?;
+        Ok(())
+    }
+
+    /// Writes the index to the file at the given path
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut dest = BufWriter::new(File::create(path)?);
+        self.write_to(&mut dest)?;
+        dest.flush()?;
+        Ok(())
+    }
+
+    /// Returns the extent covered by the index
+    pub fn extent(&self) -> &GenericBBox<Point> {
+        &self.root.bbox
+    }
+
+    /// Returns the number of shapes indexed
+    pub fn num_shapes(&self) -> usize {
+        self.num_shapes
+    }
+
+    /// Returns the maximum depth of the tree
+    pub fn max_depth(&self) -> u32 {
+        self.max_depth
+    }
+
+    /// Returns the indices (starting from 0) of the shapes that may intersect the bbox
+    ///
+    /// The shapes are found using the bboxes of the nodes, so the bbox of the shapes
+    /// returned still needs to be tested.
+    ///
+    /// The indices are sorted.
+    pub fn query(&self, bbox: &GenericBBox<Point>) -> Vec<usize> {
+        let mut shapes = Vec::new();
+        self.root.search(bbox, &mut shapes);
+        shapes.sort_unstable();
+        shapes.dedup();
+        shapes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn grid_of_points(size: usize) -> Vec<Point> {
+        (0..size * size)
+            .map(|i| Point::new((i % size) as f64, (i / size) as f64))
+            .collect()
+    }
+
+    #[test]
+    fn build_and_query() {
+        let points = grid_of_points(10);
+        let tree = QuadTree::from_shapes(&points);
+        assert_eq!(tree.num_shapes(), 100);
+        assert_eq!(tree.max_depth(), 5);
+        assert_eq!(tree.extent().max, Point::new(9.0, 9.0));
+
+        let bbox = GenericBBox {
+            min: Point::new(1.5, 1.5),
+            max: Point::new(3.0, 2.0),
+        };
+        let candidates = tree.query(&bbox);
+        assert!(candidates.len() < points.len());
+        let expected = points
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| bbox.overlaps(&GenericBBox { min: **p, max: **p }))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        assert_eq!(expected, vec![22, 23]);
+        assert!(expected.iter().all(|i| candidates.contains(i)));
+    }
+
+    #[test]
+    fn write_and_read() {
+        let points = grid_of_points(10);
+        let tree = QuadTree::from_shapes(&points);
+        let mut bytes = Vec::new();
+        tree.write_to(&mut bytes).unwrap();
+        assert_eq!(&bytes[..4], b"SQT\x01");
+        assert_eq!(
+            LittleEndian::read_u32(&bytes[QIX_HEADER_SIZE..]) as usize,
+            bytes.len() - QIX_HEADER_SIZE - NODE_SIZE - tree.root.shapes.len() * 4
+        );
+
+        let read_tree = QuadTree::read_from(Cursor::new(bytes)).unwrap();
+        assert_eq!(read_tree.num_shapes(), tree.num_shapes());
+        assert_eq!(read_tree.max_depth(), tree.max_depth());
+        let bbox = GenericBBox {
+            min: Point::new(4.0, 4.0),
+            max: Point::new(6.0, 5.0),
+        };
+        assert_eq!(read_tree.query(&bbox), tree.query(&bbox));
+    }
+
+    #[test]
+    fn invalid_signature() {
+        let bytes = vec![0u8; 64];
+        match QuadTree::read_from(Cursor::new(bytes)) {
+            Err(Error::InvalidSpatialIndex(_)) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
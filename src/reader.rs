@@ -15,6 +15,10 @@
 //! you will have to use [add_index_source](struct.Reader.html#method.add_index_source) and/or
 //! [add_dbf_source](struct.Reader.html#method.add_dbf_source)
 //!
+//! The *shx* and *dbf* sources do not have to be of the same type as the *shp* source,
+//! use [with_dbf_source](struct.Reader.html#method.with_dbf_source) to give a *dbf* source
+//! of another type.
+//!
 //! [`Read`]: https://doc.rust-lang.org/std/io/trait.Read.html
 //! [`std::fs::File`]: https://doc.rust-lang.org/std/fs/struct.File.html
 //! [`std::io::Cursor`]: https://doc.rust-lang.org/std/io/struct.Cursor.html
@@ -57,87 +61,398 @@
 //! Two functions ([read](fn.read.html) and [read_as](fn.read_as.html)) are provided to read
 //! files with one function call (thus not having to build a `Reader`)
 
+use std::fmt;
 use std::fs::File;
-use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
 use std::iter::FusedIterator;
+use std::ops::Range;
 use std::path::Path;
 
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt};
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
 
+use dbf::{encoding_from_cpg, TableReader};
+use encoding_rs::Encoding;
 use header;
+use index::{index_records, read_index_file, ShapeIndex};
+use projection::Projection;
+use qix::QuadTree;
 use record;
-use {Error, Shape};
+use record::io::bbox_read_xy_from;
+use record::GenericBBox;
+use sbn::SpatialIndex;
+use {Error, FileRole, Point, Shape, ShapeType};
 
+#[cfg(feature = "rayon")]
+use parallel::{self, ReadAt};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 use record::ReadableShape;
+#[cfg(feature = "rstar")]
+use rstar::RTree;
+#[cfg(feature = "rstar")]
+use rtree::ShapeEnvelope;
 
-const INDEX_RECORD_SIZE: usize = 2 * std::mem::size_of::<i32>();
-
-pub(crate) struct ShapeIndex {
-    pub offset: i32,
-    pub record_size: i32,
-}
-
-/// Read the content of a .shx file
-fn read_index_file<T: Read>(mut source: T) -> Result<Vec<ShapeIndex>, Error> {
-    let header = header::Header::read_from(&mut source)?;
-
-    let num_shapes = ((header.file_length * 2) - header::HEADER_SIZE) / INDEX_RECORD_SIZE as i32;
-    let mut shapes_index = Vec::<ShapeIndex>::with_capacity(num_shapes as usize);
-    for _ in 0..num_shapes {
-        let offset = source.read_i32::<BigEndian>()?;
-        let record_size = source.read_i32::<BigEndian>()?;
-        shapes_index.push(ShapeIndex {
-            offset,
-            record_size,
-        });
-    }
-    Ok(shapes_index)
-}
-
-/// Reads and returns one shape and its header from the source
+/// Reads and returns one shape from the source,
+/// with its header and the length in bytes of its content
 fn read_one_shape_as<T: Read, S: ReadableShape>(
     mut source: &mut T,
-) -> Result<(record::RecordHeader, S), Error> {
+) -> Result<(record::RecordHeader, usize, S), Error> {
     let hdr = record::RecordHeader::read_from(&mut source)?;
-    let record_size = hdr.record_size * 2;
-    let shape = S::read_from(&mut source, record_size)?;
-    Ok((hdr, shape))
+    let content_length = hdr.content_length()?;
+    let shape = S::read_from(&mut source, content_length as i32)?;
+    Ok((hdr, content_length, shape))
 }
 
 /// Struct that handle iteration over the shapes of a .shp file
 pub struct ShapeIterator<T: Read, S: ReadableShape> {
     _shape: std::marker::PhantomData<S>,
     source: T,
+    /// Index of the next record
+    current_index: usize,
     current_pos: usize,
     file_length: usize,
 }
 
-impl<T: Read, S: ReadableShape> Iterator for ShapeIterator<T, S> {
-    type Item = Result<S, Error>;
-
-    fn next(&mut self) -> Option<Self::Item> {
+impl<T: Read, S: ReadableShape> ShapeIterator<T, S> {
+    fn next_with_info(&mut self) -> Option<Result<(RecordInfo, S), Error>> {
         if self.current_pos >= self.file_length {
             None
         } else {
-            let (hdr, shape) = match read_one_shape_as::<T, S>(&mut self.source) {
-                Err(e) => return Some(Err(e)),
+            let (hdr, content_length, shape) = match read_one_shape_as::<T, S>(&mut self.source) {
+                Err(e) => {
+                    let offset = Some(self.current_pos as u64);
+                    // The position in the source is unknown, nothing more can be read
+                    self.current_pos = self.file_length;
+                    return Some(Err(e.with_context(
+                        FileRole::Shp,
+                        Some(self.current_index),
+                        offset,
+                    )));
+                }
                 Ok(hdr_and_shape) => hdr_and_shape,
             };
+            self.current_index += 1;
+            let info = RecordInfo {
+                record_number: hdr.record_number,
+                offset: self.current_pos as u64,
+                content_length,
+            };
             self.current_pos += record::RecordHeader::SIZE;
-            self.current_pos += hdr.record_size as usize * 2;
-            Some(Ok(shape))
+            self.current_pos += info.content_length;
+            Some(Ok((info, shape)))
         }
     }
 }
 
+impl<T: Read, S: ReadableShape> Iterator for ShapeIterator<T, S> {
+    type Item = Result<S, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_info()
+            .map(|result| result.map(|(_, shape)| shape))
+    }
+}
+
 impl<T: Read, S: ReadableShape> FusedIterator for ShapeIterator<T, S> {}
 
-pub struct ShapeRecordIterator<T: Read, S: ReadableShape> {
+/// Where a record is in the .shp, as read from its header
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RecordInfo {
+    /// Number of the record, as written in its header (starting from 1)
+    pub record_number: i32,
+    /// Position in bytes of the record (of its header) in the .shp
+    pub offset: u64,
+    /// Length in bytes of the content of the record
+    pub content_length: usize,
+}
+
+/// Struct that handle iteration over the shapes of a .shp file,
+/// with the position and number of their record
+pub struct ShapeInfoIterator<T: Read, S: ReadableShape> {
+    shape_iter: ShapeIterator<T, S>,
+}
+
+impl<T: Read, S: ReadableShape> Iterator for ShapeInfoIterator<T, S> {
+    type Item = Result<(RecordInfo, S), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.shape_iter.next_with_info()
+    }
+}
+
+impl<T: Read, S: ReadableShape> FusedIterator for ShapeInfoIterator<T, S> {}
+
+/// Struct that handle iteration over the shapes of a .shp file
+/// whose bbox overlaps a given bbox
+///
+/// Only the beginning of each record is read to know its bbox,
+/// the records that are not in the bbox are skipped without being decoded.
+pub struct ShapesInBBoxIterator<T: Read + Seek, S: ReadableShape> {
+    _shape: std::marker::PhantomData<S>,
+    source: T,
+    /// Index of the next record
+    current_index: usize,
+    current_pos: usize,
+    file_length: usize,
+    bbox: GenericBBox<Point>,
+}
+
+/// Size of the shape type and the bbox that start multi-part records
+const RECORD_PREFIX_SIZE: usize = std::mem::size_of::<i32>() + 4 * std::mem::size_of::<f64>();
+
+/// Returns the xy bbox of a record from the first bytes of its content,
+/// `None` for Null shapes
+fn bbox_of_record(prefix: &[u8]) -> Result<Option<GenericBBox<Point>>, Error> {
+    let mut prefix = prefix;
+    let shapetype = ShapeType::read_from(&mut prefix)?;
+    let mut bbox = GenericBBox::<Point>::default();
+    match shapetype {
+        ShapeType::NullShape => return Ok(None),
+        ShapeType::Point | ShapeType::PointM | ShapeType::PointZ => {
+            bbox.min.x = prefix.read_f64::<LittleEndian>()?;
+            bbox.min.y = prefix.read_f64::<LittleEndian>()?;
+            bbox.max = bbox.min;
+        }
+        _ => bbox_read_xy_from(&mut bbox, &mut prefix)?,
+    }
+    Ok(Some(bbox))
+}
+
+/// Reads the content of a record if its bbox overlaps `bbox`,
+/// otherwise moves the source to the end of the record and returns `None`
+fn read_record_in_bbox<T: Read + Seek, S: ReadableShape>(
+    source: &mut T,
+    record_size: usize,
+    bbox: &GenericBBox<Point>,
+) -> Result<Option<S>, Error> {
+    let mut prefix = [0u8; RECORD_PREFIX_SIZE];
+    let prefix_len = record_size.min(RECORD_PREFIX_SIZE);
+    source.read_exact(&mut prefix[..prefix_len])?;
+    let prefix = &prefix[..prefix_len];
+
+    let is_in_bbox = match bbox_of_record(prefix)? {
+        Some(record_bbox) => record_bbox.overlaps(bbox),
+        None => false,
+    };
+    if is_in_bbox {
+        let mut record_source = prefix.chain(source);
+        let shape = S::read_from(&mut record_source, record_size as i32)?;
+        Ok(Some(shape))
+    } else {
+        let remaining = (record_size - prefix_len) as i64;
+        source.seek(SeekFrom::Current(remaining))?;
+        Ok(None)
+    }
+}
+
+impl<T: Read + Seek, S: ReadableShape> ShapesInBBoxIterator<T, S> {
+    /// Reads the next record, returns `None` if it is not in the bbox
+    fn read_next_record(&mut self) -> Result<Option<S>, Error> {
+        let hdr = record::RecordHeader::read_from(&mut self.source)?;
+        let record_size = hdr.content_length()?;
+        self.current_pos += record::RecordHeader::SIZE + record_size;
+        read_record_in_bbox(&mut self.source, record_size, &self.bbox)
+    }
+}
+
+impl<T: Read + Seek, S: ReadableShape> Iterator for ShapesInBBoxIterator<T, S> {
+    type Item = Result<S, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.current_pos < self.file_length {
+            let offset = self.current_pos as u64;
+            let result = self.read_next_record();
+            self.current_index += 1;
+            match result {
+                Ok(Some(shape)) => return Some(Ok(shape)),
+                Ok(None) => continue,
+                Err(e) => {
+                    // The position of the next record is unknown
+                    self.current_pos = self.file_length;
+                    let index = Some(self.current_index - 1);
+                    return Some(Err(e.with_context(FileRole::Shp, index, Some(offset))));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<T: Read + Seek, S: ReadableShape> FusedIterator for ShapesInBBoxIterator<T, S> {}
+
+/// A record that could not be read by a [LenientShapeIterator](struct.LenientShapeIterator.html)
+#[derive(Debug)]
+pub struct SkippedRecord {
+    /// Number of the record, starting from 1 like in the .shp
+    pub record_number: usize,
+    /// Position in bytes of the record (of its header) in the .shp
+    pub offset: u64,
+    /// Why the record was skipped
+    pub error: Error,
+}
+
+impl fmt::Display for SkippedRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "record {} at byte {}: {}",
+            self.record_number, self.offset, self.error
+        )
+    }
+}
+
+/// Struct that handle iteration over the shapes of a .shp file,
+/// skipping the records that cannot be read
+///
+/// Each record is read entirely before being decoded, so that a record that
+/// cannot be decoded (unknown shape type, invalid size, ...) does not prevent
+/// reading the next ones. The next record is found using the *.shx* if it was given,
+/// otherwise using the content length declared in the header of the record.
+///
+/// Without *.shx*, the iteration stops at the first record whose content length
+/// is invalid, as the position of the next record is unknown.
+///
+/// The skipped records are given by [skipped](#method.skipped)
+/// once the iteration is over.
+pub struct LenientShapeIterator<T: Read + Seek, S: ReadableShape> {
+    _shape: std::marker::PhantomData<S>,
+    source: T,
+    shapes_index: Option<Vec<ShapeIndex>>,
+    /// Number of records already read (or skipped)
+    num_records: usize,
+    /// Position of the next record when there is no *.shx*
+    next_pos: u64,
+    file_length: u64,
+    buffer: Vec<u8>,
+    skipped: Vec<SkippedRecord>,
+}
+
+impl<T: Read + Seek, S: ReadableShape> LenientShapeIterator<T, S> {
+    /// Returns the records skipped so far
+    pub fn skipped(&self) -> &[SkippedRecord] {
+        &self.skipped
+    }
+
+    /// Consumes the iterator and returns the skipped records
+    pub fn into_skipped(self) -> Vec<SkippedRecord> {
+        self.skipped
+    }
+
+    /// Returns the position of the next record, `None` at the end of the file
+    fn next_record_offset(&self) -> Option<u64> {
+        match self.shapes_index {
+            Some(ref shapes_index) => shapes_index
+                .get(self.num_records)
+                .map(ShapeIndex::offset_in_bytes),
+            None if self.next_pos < self.file_length => Some(self.next_pos),
+            None => None,
+        }
+    }
+
+    /// Reads the record at the offset and decodes its shape
+    fn read_record(&mut self, offset: u64) -> Result<S, Error> {
+        self.source.seek(SeekFrom::Start(offset))?;
+        let hdr = record::RecordHeader::read_from(&mut self.source)?;
+        let content_start = offset + record::RecordHeader::SIZE as u64;
+        let record_size = hdr.content_length()? as u64;
+        if content_start + record_size > self.file_length {
+            return Err(Error::InvalidShapeRecordSize);
+        }
+        self.next_pos = content_start + record_size;
+
+        // The file length is untrusted too, the buffer only grows with the bytes read
+        self.buffer.clear();
+        (&mut self.source)
+            .take(record_size)
+            .read_to_end(&mut self.buffer)?;
+        if (self.buffer.len() as u64) < record_size {
+            return Err(Error::InvalidShapeRecordSize);
+        }
+        S::read_from(&mut self.buffer.as_slice(), record_size as i32)
+    }
+}
+
+impl<T: Read + Seek, S: ReadableShape> Iterator for LenientShapeIterator<T, S> {
+    type Item = S;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(offset) = self.next_record_offset() {
+            let previous_pos = self.next_pos;
+            let result = self.read_record(offset);
+            self.num_records += 1;
+            match result {
+                Ok(shape) => return Some(shape),
+                Err(error) => {
+                    if self.shapes_index.is_none() && self.next_pos == previous_pos {
+                        // The position of the next record is unknown
+                        self.next_pos = self.file_length;
+                    }
+                    self.skipped.push(SkippedRecord {
+                        record_number: self.num_records,
+                        offset,
+                        error,
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<T: Read + Seek, S: ReadableShape> FusedIterator for LenientShapeIterator<T, S> {}
+
+/// Iterator over the bytes of each record of an in-memory .shp
+///
+/// Each item is the content of a record (starting with its shape type),
+/// without the record header.
+pub struct RecordBytesIterator<'a> {
+    bytes: &'a [u8],
+    /// Index of the next record
+    current_index: usize,
+    current_pos: usize,
+    file_length: usize,
+}
+
+impl<'a> Iterator for RecordBytesIterator<'a> {
+    type Item = Result<&'a [u8], Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.current_pos >= self.file_length {
+            return None;
+        }
+        let hdr = match self.bytes.get(self.current_pos..) {
+            Some(mut remaining) => record::RecordHeader::read_from(&mut remaining),
+            None => Err(Error::IoError(std::io::ErrorKind::UnexpectedEof.into())),
+        };
+        let start = self.current_pos + record::RecordHeader::SIZE;
+        let record = hdr.and_then(|hdr| {
+            let end = start + hdr.record_size.max(0) as usize * 2;
+            self.bytes
+                .get(start..end)
+                .ok_or_else(|| Error::IoError(std::io::ErrorKind::UnexpectedEof.into()))
+        });
+        let offset = self.current_pos as u64;
+        match record {
+            Ok(bytes) => self.current_pos = start + bytes.len(),
+            // The position of the next record is unknown
+            Err(_) => self.current_pos = self.file_length,
+        }
+        self.current_index += 1;
+        let index = Some(self.current_index - 1);
+        Some(record.map_err(|e| e.with_context(FileRole::Shp, index, Some(offset))))
+    }
+}
+
+impl<'a> FusedIterator for RecordBytesIterator<'a> {}
+
+pub struct ShapeRecordIterator<T: Read, S: ReadableShape, D: Read = T> {
     shape_iter: ShapeIterator<T, S>,
-    dbf_reader: dbase::Reader<T>,
+    dbf_reader: TableReader<D>,
 }
 
-impl<T: Read, S: ReadableShape> Iterator for ShapeRecordIterator<T, S> {
+impl<T: Read, S: ReadableShape, D: Read> Iterator for ShapeRecordIterator<T, S, D> {
     type Item = Result<(S, dbase::Record), Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -147,7 +462,7 @@ impl<T: Read, S: ReadableShape> Iterator for ShapeRecordIterator<T, S> {
         };
 
         let record = match self.dbf_reader.next()? {
-            Err(e) => return Some(Err(Error::DbaseError(e))),
+            Err(e) => return Some(Err(e)),
             Ok(rcd) => rcd,
         };
 
@@ -155,15 +470,20 @@ impl<T: Read, S: ReadableShape> Iterator for ShapeRecordIterator<T, S> {
     }
 }
 
-impl<T: Read, S: ReadableShape> FusedIterator for ShapeRecordIterator<T, S> {}
+impl<T: Read, S: ReadableShape, D: Read> FusedIterator for ShapeRecordIterator<T, S, D> {}
 
-//TODO Make it possible for the dbf source to be of a different dtype ?
 /// struct that reads the content of a shapefile
-pub struct Reader<T: Read> {
+///
+/// `T` is the type of the *shp* source and `D` the type of the *dbf* source,
+/// by default both are the same.
+pub struct Reader<T: Read, D: Read = T> {
     source: T,
     header: header::Header,
     shapes_index: Option<Vec<ShapeIndex>>,
-    dbf_reader: Option<dbase::Reader<T>>,
+    spatial_index: Option<SpatialIndex>,
+    quad_tree: Option<QuadTree>,
+    projection: Option<Projection>,
+    dbf_reader: Option<TableReader<D>>,
 }
 
 impl<T: Read> Reader<T> {
@@ -187,16 +507,22 @@ impl<T: Read> Reader<T> {
     /// let reader = shapefile::Reader::new(file).unwrap();
     /// ```
     pub fn new(mut source: T) -> Result<Reader<T>, Error> {
-        let header = header::Header::read_from(&mut source)?;
+        let header = header::Header::read_from(&mut source)
+            .map_err(|e| e.with_context(FileRole::Shp, None, Some(0)))?;
 
         Ok(Reader {
             source,
             header,
             shapes_index: None,
+            spatial_index: None,
+            quad_tree: None,
+            projection: None,
             dbf_reader: None,
         })
     }
+}
 
+impl<T: Read, D: Read> Reader<T, D> {
     /// Returns a non-mutable reference to the header read
     ///
     /// # Examples
@@ -258,7 +584,7 @@ impl<T: Read> Reader<T> {
     /// Read and return _only_ the records contained in the *.dbf* file
     pub fn read_records(self) -> Result<Vec<dbase::Record>, Error> {
         let dbf_reader = self.dbf_reader.ok_or(Error::MissingDbf)?;
-        dbf_reader.read().or_else(|e| Err(Error::DbaseError(e)))
+        dbf_reader.read()
     }
 
     /// Returns an iterator that tries to read the shapes as the specified type
@@ -277,8 +603,9 @@ impl<T: Read> Reader<T> {
         ShapeIterator {
             _shape: std::marker::PhantomData,
             source: self.source,
+            current_index: 0,
             current_pos: header::HEADER_SIZE as usize,
-            file_length: (self.header.file_length * 2) as usize,
+            file_length: self.header.file_size(),
         }
     }
 
@@ -310,11 +637,38 @@ impl<T: Read> Reader<T> {
         ShapeIterator {
             _shape: std::marker::PhantomData,
             source: self.source,
+            current_index: 0,
             current_pos: header::HEADER_SIZE as usize,
-            file_length: (self.header.file_length * 2) as usize,
+            file_length: self.header.file_size(),
+        }
+    }
+
+    /// Returns an iterator over the shapes of the file, which must all be of type `S`,
+    /// with the number and position of their record
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let reader = shapefile::Reader::from_path("tests/data/pointm.shp").unwrap();
+    /// for result in reader.iter_shapes_with_info_as::<shapefile::PointM>() {
+    ///     let (info, point) = result.unwrap();
+    ///     println!("record {} at byte {}: {}", info.record_number, info.offset, point);
+    /// }
+    /// ```
+    pub fn iter_shapes_with_info_as<S: ReadableShape>(self) -> ShapeInfoIterator<T, S> {
+        ShapeInfoIterator {
+            shape_iter: self.iter_shapes_as::<S>(),
         }
     }
 
+    /// Returns an iterator over the shapes of the file,
+    /// with the number and position of their record
+    ///
+    /// (see [iter_shapes_with_info_as](struct.Reader.html#method.iter_shapes_with_info_as))
+    pub fn iter_shapes_with_info(self) -> ShapeInfoIterator<T, Shape> {
+        self.iter_shapes_with_info_as::<Shape>()
+    }
+
     /// Returns an iterator over the Shapes and their Records
     ///
     /// # Errors
@@ -332,7 +686,7 @@ impl<T: Read> Reader<T> {
     /// ```
     pub fn iter_shapes_and_records_as<S: ReadableShape>(
         mut self,
-    ) -> Result<ShapeRecordIterator<T, S>, Error> {
+    ) -> Result<ShapeRecordIterator<T, S, D>, Error> {
         let maybe_dbf_reader = self.dbf_reader.take();
         if let Some(dbf_reader) = maybe_dbf_reader {
             let shape_iter = self.iter_shapes_as::<S>();
@@ -365,7 +719,7 @@ impl<T: Read> Reader<T> {
     ///     // ...
     /// }
     /// ```
-    pub fn iter_shapes_and_records(self) -> Result<ShapeRecordIterator<T, Shape>, Error> {
+    pub fn iter_shapes_and_records(self) -> Result<ShapeRecordIterator<T, Shape, D>, Error> {
         self.iter_shapes_and_records_as::<Shape>()
     }
 
@@ -373,20 +727,141 @@ impl<T: Read> Reader<T> {
     /// This allows to later read shapes by giving their index without reading the whole file
     ///
     /// (see [read_nth_shape()](struct.Reader.html#method.read_nth_shape))
-    pub fn add_index_source(&mut self, source: T) -> Result<(), Error> {
+    ///
+    /// The index source can be of any type, it is entirely read by this method.
+    pub fn add_index_source<I: Read>(&mut self, source: I) -> Result<(), Error> {
         self.shapes_index = Some(read_index_file(source)?);
         Ok(())
     }
 
+    /// Reads the .sbn spatial index from the source
+    ///
+    /// The spatial index is used by
+    /// [read_shapes_in_bbox_as](struct.Reader.html#method.read_shapes_in_bbox_as)
+    /// to only read the shapes that may be in the bbox.
+    pub fn add_spatial_index_source<I: Read>(&mut self, source: I) -> Result<(), Error> {
+        self.spatial_index = Some(SpatialIndex::read_from(source)?);
+        Ok(())
+    }
+
+    /// Reads the .qix quadtree index from the source
+    ///
+    /// Like the .sbn, it is used by
+    /// [read_shapes_in_bbox_as](struct.Reader.html#method.read_shapes_in_bbox_as),
+    /// when both are given, the .qix is the one used.
+    pub fn add_quad_tree_source<I: Read>(&mut self, source: I) -> Result<(), Error> {
+        self.quad_tree = Some(QuadTree::read_from(source)?);
+        Ok(())
+    }
+
+    /// Reads the .prj from the source
+    pub fn add_projection_source<I: Read>(&mut self, source: I) -> Result<(), Error> {
+        self.projection = Some(Projection::read_from(source)?);
+        Ok(())
+    }
+
+    /// Returns the coordinate reference system of the shapes, if a .prj was read
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let reader = shapefile::Reader::from_path("tests/data/line.shp").unwrap();
+    /// assert!(reader.projection().is_none());
+    /// ```
+    pub fn projection(&self) -> Option<&Projection> {
+        self.projection.as_ref()
+    }
+
     /// Adds the `source` as the source where the dbf record will be read from
-    pub fn add_dbf_source(&mut self, source: T) -> Result<(), Error> {
-        let dbf_reader = dbase::Reader::new(source)?;
+    pub fn add_dbf_source(&mut self, source: D) -> Result<(), Error> {
+        let dbf_reader = TableReader::new(source)?;
         self.dbf_reader = Some(dbf_reader);
         Ok(())
     }
+
+    /// Sets the encoding used to decode the text of the dbf records, UTF-8 by default
+    ///
+    /// [from_path](struct.Reader.html#method.from_path) sets the encoding declared
+    /// in the .cpg, see [encoding_from_cpg](../dbf/fn.encoding_from_cpg.html).
+    ///
+    /// # Errors
+    ///
+    /// `Error::MissingDbf` if no *.dbf* was given
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use shapefile::encoding_rs::WINDOWS_1252;
+    /// let mut reader = shapefile::Reader::from_path("tests/data/multipatch.shp").unwrap();
+    /// reader.set_dbf_encoding(WINDOWS_1252).unwrap();
+    /// let records = reader.read_records().unwrap();
+    /// ```
+    pub fn set_dbf_encoding(&mut self, encoding: &'static Encoding) -> Result<(), Error> {
+        match self.dbf_reader {
+            Some(ref mut dbf_reader) => {
+                dbf_reader.set_encoding(encoding);
+                Ok(())
+            }
+            None => Err(Error::MissingDbf),
+        }
+    }
+
+    /// Returns the encoding used to decode the text of the dbf records,
+    /// `None` if no *.dbf* was given
+    pub fn dbf_encoding(&self) -> Option<&'static Encoding> {
+        self.dbf_reader
+            .as_ref()
+            .map(|dbf_reader| dbf_reader.encoding())
+    }
+
+    /// Sets the encoding declared in the .cpg, if it is known and a .dbf was given
+    pub(crate) fn set_dbf_encoding_from_cpg<I: Read>(
+        &mut self,
+        mut source: I,
+    ) -> Result<(), Error> {
+        let mut content = String::new();
+        source.read_to_string(&mut content)?;
+        if let (Some(encoding), Some(dbf_reader)) =
+            (encoding_from_cpg(&content), &mut self.dbf_reader)
+        {
+            dbf_reader.set_encoding(encoding);
+        }
+        Ok(())
+    }
+
+    /// Returns a reader that will read the dbf records from `source`
+    ///
+    /// Unlike [add_dbf_source](struct.Reader.html#method.add_dbf_source),
+    /// the `source` does not need to be of the same type as the current dbf source.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use std::io::Cursor;
+    /// let shp = Cursor::new(std::fs::read("tests/data/multipatch.shp").unwrap());
+    /// let dbf = File::open("tests/data/multipatch.dbf").unwrap();
+    /// let reader = shapefile::Reader::new(shp)
+    ///     .and_then(|reader| reader.with_dbf_source(dbf))
+    ///     .unwrap();
+    /// let records = reader.read_records().unwrap();
+    /// assert_eq!(records.len(), 1);
+    /// ```
+    pub fn with_dbf_source<D2: Read>(self, source: D2) -> Result<Reader<T, D2>, Error> {
+        let dbf_reader = TableReader::new(source)?;
+        Ok(Reader {
+            source: self.source,
+            header: self.header,
+            shapes_index: self.shapes_index,
+            spatial_index: self.spatial_index,
+            quad_tree: self.quad_tree,
+            projection: self.projection,
+            dbf_reader: Some(dbf_reader),
+        })
+    }
 }
 
-impl<T: Read> IntoIterator for Reader<T> {
+impl<T: Read, D: Read> IntoIterator for Reader<T, D> {
     type Item = Result<Shape, Error>;
     type IntoIter = ShapeIterator<T, Shape>;
 
@@ -402,6 +877,12 @@ impl Reader<BufReader<File>> {
     /// if they do not exists the function will not fail, and you will get an error later
     /// if you try to use a function that requires the file to be present.
     ///
+    /// The .prj and the .qix and .sbn spatial indexes are also read if they exist,
+    /// and the text of the .dbf is decoded with the encoding declared in the .cpg
+    /// (an encoding that is not known is ignored).
+    /// A .sbn or .qix that cannot be read is ignored, the queries then do not use it,
+    /// and so is a .prj that is not valid WKT ([projection](#method.projection) then gives `None`).
+    ///
     ///
     /// # Examples
     ///
@@ -433,6 +914,10 @@ impl Reader<BufReader<File>> {
         let shape_path = path.as_ref().to_path_buf();
         let shx_path = shape_path.with_extension("shx");
         let dbf_path = shape_path.with_extension("dbf");
+        let sbn_path = shape_path.with_extension("sbn");
+        let qix_path = shape_path.with_extension("qix");
+        let prj_path = shape_path.with_extension("prj");
+        let cpg_path = shape_path.with_extension("cpg");
 
         let source = BufReader::new(File::open(shape_path)?);
         let mut reader = Self::new(source)?;
@@ -442,17 +927,155 @@ impl Reader<BufReader<File>> {
             reader.add_index_source(index_source)?;
         }
 
+        if let Ok(sbn_file) = File::open(sbn_path) {
+            // The .sbn only speeds up the queries, one that cannot be read is ignored
+            let _ = reader.add_spatial_index_source(BufReader::new(sbn_file));
+        }
+
+        if let Ok(qix_file) = File::open(qix_path) {
+            // Like the .sbn, a .qix that cannot be read is ignored
+            let _ = reader.add_quad_tree_source(BufReader::new(qix_file));
+        }
+
+        if let Ok(prj_file) = File::open(prj_path) {
+            // The shapes can be read without their projection, a .prj that cannot be read is ignored
+            let _ = reader.add_projection_source(prj_file);
+        }
+
         if dbf_path.exists() {
             let dbf_source = BufReader::new(File::open(dbf_path)?);
             reader.add_dbf_source(dbf_source)?;
         }
+
+        if cpg_path.exists() {
+            reader.set_dbf_encoding_from_cpg(File::open(cpg_path)?)?;
+        }
         Ok(reader)
     }
 }
 
+#[cfg(feature = "mmap")]
+impl Reader<Cursor<Mmap>> {
+    /// Creates a reader from a path to a file, the files are memory-mapped
+    ///
+    /// Like [from_path](struct.Reader.html#method.from_path) the .shx, .qix, .sbn, .prj, .dbf
+    /// and .cpg are also opened if they exist.
+    ///
+    /// The shapes are decoded like with the other sources, through a `Cursor` over the mapped bytes,
+    /// seeking (e.g. in [read_nth_shape](struct.Reader.html#method.read_nth_shape))
+    /// only moves the position of the `Cursor`.
+    /// To access the records without copying them, use
+    /// [iter_record_bytes](struct.Reader.html#method.iter_record_bytes) with the
+    /// views of the [view](../record/view/index.html) module.
+    ///
+    /// # Important
+    ///
+    /// The files must not be modified (by this process or another one) while they are mapped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(feature = "mmap")]
+    /// # {
+    /// let reader = shapefile::Reader::from_path_mmap("tests/data/line.shp").unwrap();
+    /// let polylines = reader.read_as::<shapefile::Polyline>().unwrap();
+    /// # }
+    /// ```
+    pub fn from_path_mmap<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let shape_path = path.as_ref().to_path_buf();
+        let shx_path = shape_path.with_extension("shx");
+        let dbf_path = shape_path.with_extension("dbf");
+        let sbn_path = shape_path.with_extension("sbn");
+        let qix_path = shape_path.with_extension("qix");
+        let prj_path = shape_path.with_extension("prj");
+        let cpg_path = shape_path.with_extension("cpg");
+
+        let mut reader = Self::new(Cursor::new(map_file(&shape_path)?))?;
+
+        if shx_path.exists() {
+            reader.add_index_source(Cursor::new(map_file(&shx_path)?))?;
+        }
+
+        if let Ok(sbn_map) = map_file(&sbn_path) {
+            // The .sbn only speeds up the queries, one that cannot be read is ignored
+            let _ = reader.add_spatial_index_source(Cursor::new(sbn_map));
+        }
+
+        if let Ok(qix_map) = map_file(&qix_path) {
+            // Like the .sbn, a .qix that cannot be read is ignored
+            let _ = reader.add_quad_tree_source(Cursor::new(qix_map));
+        }
+
+        if let Ok(prj_file) = File::open(prj_path) {
+            // The shapes can be read without their projection, a .prj that cannot be read is ignored
+            let _ = reader.add_projection_source(prj_file);
+        }
+
+        if dbf_path.exists() {
+            reader.add_dbf_source(Cursor::new(map_file(&dbf_path)?))?;
+        }
+
+        if cpg_path.exists() {
+            reader.set_dbf_encoding_from_cpg(File::open(cpg_path)?)?;
+        }
+        Ok(reader)
+    }
+}
+
+#[cfg(feature = "mmap")]
+fn map_file(path: &Path) -> Result<Mmap, Error> {
+    let file = File::open(path)?;
+    // Safety: as documented in from_path_mmap, the file must not be modified while mapped
+    let map = unsafe { Mmap::map(&file)? };
+    Ok(map)
+}
+
+/// Sources that are in memory give access to the bytes of the records
+impl<B: AsRef<[u8]>, D: Read> Reader<Cursor<B>, D> {
+    /// Returns an iterator over the bytes of each record, without copying them
+    ///
+    /// The bytes can be wrapped in the views of the [view](../record/view/index.html)
+    /// module to access the shapes without allocating.
+    pub fn iter_record_bytes<'a>(&'a self) -> RecordBytesIterator<'a> {
+        RecordBytesIterator {
+            bytes: self.source.get_ref().as_ref(),
+            current_index: 0,
+            current_pos: header::HEADER_SIZE as usize,
+            file_length: self.header.file_size(),
+        }
+    }
+}
+
 /// Sources that implements `Seek` have access to
 /// a few more methods that uses the *index file(.shx)*
-impl<T: Read + Seek> Reader<T> {
+impl<T: Read + Seek, D: Read> Reader<T, D> {
+    /// Builds the index of the shapes by reading the headers of all the records of the .shp,
+    /// replacing the *.shx* if one was given
+    ///
+    /// The index is what allows to read shapes by giving their index,
+    /// it is built automatically by the methods that need it when no *.shx* was given.
+    /// To write the index as a .shx, see [index::rebuild](../index/fn.rebuild.html).
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidShapeRecordSize` if the content length declared by a record is invalid
+    pub fn build_index(&mut self) -> Result<(), Error> {
+        self.source
+            .seek(SeekFrom::Start(header::HEADER_SIZE as u64))?;
+        let shapes_index = index_records(&mut self.source, &self.header);
+        self.source
+            .seek(SeekFrom::Start(header::HEADER_SIZE as u64))?;
+        self.shapes_index = Some(shapes_index?);
+        Ok(())
+    }
+
+    fn build_index_if_missing(&mut self) -> Result<(), Error> {
+        if self.shapes_index.is_none() {
+            self.build_index()?;
+        }
+        Ok(())
+    }
+
     /// Reads the `n`th shape of the shapefile
     ///
     /// # Important
@@ -464,26 +1087,35 @@ impl<T: Read + Seek> Reader<T> {
     ///
     /// `None` if the index is out of range
     ///
-    /// # Errors
+    /// # Index
     ///
-    /// This method will return an `Error::MissingIndexFile` if you use it
-    /// but no *.shx* was found when opening the shapefile.
+    /// When no *.shx* was given, the index is built by reading the .shp the first time
+    /// (see [build_index](struct.Reader.html#method.build_index)).
     pub fn read_nth_shape_as<S: ReadableShape>(
         &mut self,
         index: usize,
     ) -> Option<Result<S, Error>> {
+        if let Err(e) = self.build_index_if_missing() {
+            return Some(Err(e));
+        }
         if let Some(ref shapes_index) = self.shapes_index {
             let offset = {
                 let shape_idx = shapes_index.get(index)?;
-                (shape_idx.offset * 2) as u64
+                shape_idx.offset_in_bytes()
             };
 
             if let Err(e) = self.source.seek(SeekFrom::Start(offset)) {
                 return Some(Err(Error::IoError(e)));
             }
 
-            let (_, shape) = match read_one_shape_as::<T, S>(&mut self.source) {
-                Err(e) => return Some(Err(e)),
+            let (_, _, shape) = match read_one_shape_as::<T, S>(&mut self.source) {
+                Err(e) => {
+                    return Some(Err(e.with_context(
+                        FileRole::Shp,
+                        Some(index),
+                        Some(offset),
+                    )))
+                }
                 Ok(hdr_and_shape) => hdr_and_shape,
             };
 
@@ -503,6 +1135,344 @@ impl<T: Read + Seek> Reader<T> {
     pub fn read_nth_shape(&mut self, index: usize) -> Option<Result<Shape, Error>> {
         self.read_nth_shape_as::<Shape>(index)
     }
+
+    /// Returns an iterator over the shapes of the file, which must all be of type `S`,
+    /// that skips the records that cannot be read instead of stopping
+    ///
+    /// (see [LenientShapeIterator](struct.LenientShapeIterator.html))
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let reader = shapefile::Reader::from_path("tests/data/line.shp").unwrap();
+    /// let mut shapes = reader.iter_shapes_lenient_as::<shapefile::Polyline>();
+    /// for polyline in &mut shapes {
+    ///     println!("{}", polyline);
+    /// }
+    /// for skipped in shapes.skipped() {
+    ///     eprintln!("skipped {}", skipped);
+    /// }
+    /// ```
+    pub fn iter_shapes_lenient_as<S: ReadableShape>(self) -> LenientShapeIterator<T, S> {
+        LenientShapeIterator {
+            _shape: std::marker::PhantomData,
+            source: self.source,
+            shapes_index: self.shapes_index,
+            num_records: 0,
+            next_pos: header::HEADER_SIZE as u64,
+            file_length: self.header.file_size() as u64,
+            buffer: Vec::new(),
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Returns an iterator over the shapes of the file
+    /// that skips the records that cannot be read
+    ///
+    /// (see [iter_shapes_lenient_as](struct.Reader.html#method.iter_shapes_lenient_as))
+    pub fn iter_shapes_lenient(self) -> LenientShapeIterator<T, Shape> {
+        self.iter_shapes_lenient_as::<Shape>()
+    }
+
+    /// Returns an iterator over the shapes whose bbox overlaps the given `bbox`
+    ///
+    /// Only the x and y dimensions are compared, using the bbox stored
+    /// in each record (or the point itself for point records).
+    /// The records that do not overlap are skipped by seeking over their content,
+    /// so their points are never decoded.
+    ///
+    /// Null shapes are never returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use shapefile::{Point, Reader};
+    /// use shapefile::record::GenericBBox;
+    /// let reader = Reader::from_path("tests/data/line.shp").unwrap();
+    /// let bbox = GenericBBox { min: Point::new(0.0, 0.0), max: Point::new(2.0, 2.0) };
+    /// for polyline in reader.iter_shapes_in_bbox_as::<shapefile::Polyline>(bbox) {
+    ///     let polyline = polyline.unwrap();
+    ///     assert!(polyline.bbox().overlaps(&bbox));
+    /// }
+    /// ```
+    pub fn iter_shapes_in_bbox_as<S: ReadableShape>(
+        self,
+        bbox: GenericBBox<Point>,
+    ) -> ShapesInBBoxIterator<T, S> {
+        ShapesInBBoxIterator {
+            _shape: std::marker::PhantomData,
+            source: self.source,
+            current_index: 0,
+            current_pos: header::HEADER_SIZE as usize,
+            file_length: self.header.file_size(),
+            bbox,
+        }
+    }
+
+    /// Returns an iterator over the shapes whose bbox overlaps the given `bbox`
+    ///
+    /// (see [iter_shapes_in_bbox_as](struct.Reader.html#method.iter_shapes_in_bbox_as))
+    pub fn iter_shapes_in_bbox(self, bbox: GenericBBox<Point>) -> ShapesInBBoxIterator<T, Shape> {
+        self.iter_shapes_in_bbox_as::<Shape>(bbox)
+    }
+
+    /// Reads the shapes whose bbox overlaps the given `bbox`, with their index
+    ///
+    /// The *.shx* is used to go directly to the shapes, and if a *.qix* or *.sbn*
+    /// spatial index was given, only the shapes it returns are looked at.
+    /// Otherwise, the bbox of every shape is read
+    /// (but like [iter_shapes_in_bbox_as](struct.Reader.html#method.iter_shapes_in_bbox_as)
+    /// only the shapes in the bbox are decoded).
+    ///
+    /// The indexes can be given to [read_nth_feature](struct.Reader.html#method.read_nth_feature)
+    /// to get the record of the shapes.
+    ///
+    /// When no *.shx* was given, the index is built by reading the .shp the first time
+    /// (see [build_index](struct.Reader.html#method.build_index)).
+    pub fn read_shapes_in_bbox_as<S: ReadableShape>(
+        &mut self,
+        bbox: GenericBBox<Point>,
+    ) -> Result<Vec<(usize, S)>, Error> {
+        self.build_index_if_missing()?;
+        let num_shapes = match self.shapes_index {
+            Some(ref shapes_index) => shapes_index.len(),
+            None => return Err(Error::MissingIndexFile),
+        };
+        let candidates = match (&self.quad_tree, &self.spatial_index) {
+            (Some(quad_tree), _) => quad_tree.query(&bbox),
+            (None, Some(spatial_index)) => spatial_index.query(&bbox),
+            (None, None) => (0..num_shapes).collect(),
+        };
+
+        let mut shapes = Vec::<(usize, S)>::new();
+        for index in candidates {
+            let offset = match self.shapes_index.as_ref().and_then(|idx| idx.get(index)) {
+                Some(shape_idx) => shape_idx.offset_in_bytes(),
+                None => {
+                    return Err(Error::InvalidSpatialIndex(format!(
+                        "the shape {} is not in the index file",
+                        index
+                    )))
+                }
+            };
+            let shape = self
+                .read_record_in_bbox_at(offset, &bbox)
+                .map_err(|e| e.with_context(FileRole::Shp, Some(index), Some(offset)))?;
+            if let Some(shape) = shape {
+                shapes.push((index, shape));
+            }
+        }
+        self.source
+            .seek(SeekFrom::Start(header::HEADER_SIZE as u64))?;
+        Ok(shapes)
+    }
+
+    /// Reads the shapes whose bbox overlaps the given `bbox`, with their index
+    ///
+    /// (see [read_shapes_in_bbox_as](struct.Reader.html#method.read_shapes_in_bbox_as))
+    pub fn read_shapes_in_bbox(
+        &mut self,
+        bbox: GenericBBox<Point>,
+    ) -> Result<Vec<(usize, Shape)>, Error> {
+        self.read_shapes_in_bbox_as::<Shape>(bbox)
+    }
+
+    /// Reads the record at `offset` if its bbox overlaps `bbox`
+    fn read_record_in_bbox_at<S: ReadableShape>(
+        &mut self,
+        offset: u64,
+        bbox: &GenericBBox<Point>,
+    ) -> Result<Option<S>, Error> {
+        self.source.seek(SeekFrom::Start(offset))?;
+        let hdr = record::RecordHeader::read_from(&mut self.source)?;
+        read_record_in_bbox(&mut self.source, hdr.content_length()?, bbox)
+    }
+
+    /// Builds an R-tree of the bbox of the shapes
+    ///
+    /// Only the beginning of the records, which contains their bbox, is read:
+    /// the *.shx* is used to go from one record to the other.
+    /// Null shapes are not in the tree.
+    ///
+    /// The indices of the shapes found by querying the tree can be given to
+    /// [read_nth_shape](struct.Reader.html#method.read_nth_shape)
+    /// (see the [rtree](../rtree/index.html) module).
+    ///
+    /// When no *.shx* was given, the index is built by reading the .shp the first time
+    /// (see [build_index](struct.Reader.html#method.build_index)).
+    ///
+    /// Requires the `rstar` feature.
+    #[cfg(feature = "rstar")]
+    pub fn build_rtree(&mut self) -> Result<RTree<ShapeEnvelope>, Error> {
+        self.build_index_if_missing()?;
+        let offsets = match self.shapes_index {
+            Some(ref shapes_index) => shapes_index
+                .iter()
+                .map(ShapeIndex::offset_in_bytes)
+                .collect::<Vec<_>>(),
+            None => return Err(Error::MissingIndexFile),
+        };
+
+        let mut envelopes = Vec::<ShapeEnvelope>::with_capacity(offsets.len());
+        let mut prefix = [0u8; RECORD_PREFIX_SIZE];
+        for (index, offset) in offsets.into_iter().enumerate() {
+            self.source.seek(SeekFrom::Start(offset))?;
+            let hdr = record::RecordHeader::read_from(&mut self.source)?;
+            let prefix_len = hdr
+                .content_length()
+                .map_err(|e| e.with_context(FileRole::Shp, Some(index), Some(offset)))?
+                .min(RECORD_PREFIX_SIZE);
+            self.source.read_exact(&mut prefix[..prefix_len])?;
+            if let Some(bbox) = bbox_of_record(&prefix[..prefix_len])? {
+                envelopes.push(ShapeEnvelope { index, bbox });
+            }
+        }
+        self.source
+            .seek(SeekFrom::Start(header::HEADER_SIZE as u64))?;
+        Ok(RTree::bulk_load(envelopes))
+    }
+}
+
+#[cfg(feature = "rayon")]
+/// Sources that can be read at any position allow to decode the shapes in parallel
+///
+/// Requires the `rayon` feature.
+impl<T: Read + Seek + ReadAt, D: Read> Reader<T, D> {
+    /// Returns a parallel iterator over the shapes of the file,
+    /// which must all be of type `S`
+    ///
+    /// The records are read using the *.shx*, in chunks decoded on rayon's thread pool.
+    /// When no *.shx* was given, the index is built first (see [build_index](#method.build_index)).
+    /// Collecting the iterator gives the shapes in the order of the file.
+    ///
+    /// When reading a chunk fails, its error is the only item given for the chunk,
+    /// an entry of the *.shx* that does not point to a record of the *.shp*
+    /// is an `Error::InvalidShapeRecordSize`.
+    pub fn par_iter_shapes_as<'a, S: ReadableShape + Send + 'a>(
+        &'a mut self,
+    ) -> Result<impl ParallelIterator<Item = Result<S, Error>> + 'a, Error> {
+        self.build_index_if_missing()?;
+        let shapes_index = self.shapes_index.as_ref().ok_or(Error::MissingIndexFile)?;
+        let file_size = self.header.file_size() as u64;
+        let source = &self.source;
+        Ok(shapes_index
+            .par_chunks(parallel::RECORDS_PER_CHUNK)
+            .enumerate()
+            .flat_map_iter(move |(i, chunk)| {
+                let first_index = i * parallel::RECORDS_PER_CHUNK;
+                match parallel::decode_chunk::<T, S>(source, chunk, first_index, file_size) {
+                    Ok(shapes) => shapes.into_iter().map(Ok).collect::<Vec<_>>(),
+                    Err(e) => vec![Err(e)],
+                }
+            }))
+    }
+
+    /// Returns a parallel iterator over the shapes of the file
+    ///
+    /// (see [par_iter_shapes_as](struct.Reader.html#method.par_iter_shapes_as))
+    pub fn par_iter_shapes(
+        &mut self,
+    ) -> Result<impl ParallelIterator<Item = Result<Shape, Error>> + '_, Error> {
+        self.par_iter_shapes_as::<Shape>()
+    }
+
+    /// Reads all the shapes of the file, which must all be of type `S`,
+    /// decoding them in parallel
+    ///
+    /// The shapes are in the order of the file.
+    pub fn par_read_as<S: ReadableShape + Send>(&mut self) -> Result<Vec<S>, Error> {
+        self.par_iter_shapes_as::<S>()?.collect()
+    }
+
+    /// Reads all the shapes of the file, decoding them in parallel
+    ///
+    /// (see [par_read_as](struct.Reader.html#method.par_read_as))
+    pub fn par_read(&mut self) -> Result<Vec<Shape>, Error> {
+        self.par_read_as::<Shape>()
+    }
+}
+
+/// When both the *shp* and *dbf* sources implements `Seek`,
+/// shapes can be read together with their records by index
+impl<T: Read + Seek, D: Read + Seek> Reader<T, D> {
+    /// Reads the `n`th shape of the shapefile and its record
+    ///
+    /// Like [read_nth_shape_as](struct.Reader.html#method.read_nth_shape_as)
+    /// indexes start from 0, the record is found using the fixed record size of the *dbf*.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the index is out of range of either the shapes or the records
+    ///
+    /// # Errors
+    ///
+    /// `Error::MissingDbf` if no *.dbf* was given.
+    pub fn read_nth_feature_as<S: ReadableShape>(
+        &mut self,
+        index: usize,
+    ) -> Option<Result<(S, dbase::Record), Error>> {
+        if self.dbf_reader.is_none() {
+            return Some(Err(Error::MissingDbf));
+        }
+
+        let shape = match self.read_nth_shape_as::<S>(index)? {
+            Err(e) => return Some(Err(e)),
+            Ok(shape) => shape,
+        };
+
+        let record = match self.dbf_reader {
+            Some(ref mut dbf_reader) => match dbf_reader.read_nth_record(index)? {
+                Err(e) => return Some(Err(e)),
+                Ok(record) => record,
+            },
+            None => return Some(Err(Error::MissingDbf)),
+        };
+        Some(Ok((shape, record)))
+    }
+
+    /// Reads the `n`th shape of the shapefile and its record
+    pub fn read_nth_feature(
+        &mut self,
+        index: usize,
+    ) -> Option<Result<(Shape, dbase::Record), Error>> {
+        self.read_nth_feature_as::<Shape>(index)
+    }
+
+    /// Reads the shapes and records whose indexes are in `range`
+    ///
+    /// Indexes past the last shape (or record) are ignored, so the returned
+    /// `Vec` may have less elements than the range.
+    ///
+    /// # Errors
+    ///
+    /// Same errors as [read_nth_feature_as](struct.Reader.html#method.read_nth_feature_as)
+    pub fn read_range_as<S: ReadableShape>(
+        &mut self,
+        range: Range<usize>,
+    ) -> Result<Vec<(S, dbase::Record)>, Error> {
+        let num_records = match self.dbf_reader {
+            Some(ref dbf_reader) => dbf_reader.num_records(),
+            None => return Err(Error::MissingDbf),
+        };
+        // Indexes past the last record are not read, no need to allocate for them
+        let range = range.start..range.end.min(num_records).max(range.start);
+        let mut features = Vec::<(S, dbase::Record)>::with_capacity(range.len());
+        for index in range {
+            match self.read_nth_feature_as::<S>(index) {
+                Some(feature) => features.push(feature?),
+                None => break,
+            }
+        }
+        Ok(features)
+    }
+
+    /// Reads the shapes and records whose indexes are in `range`
+    pub fn read_range(
+        &mut self,
+        range: Range<usize>,
+    ) -> Result<Vec<(Shape, dbase::Record)>, Error> {
+        self.read_range_as::<Shape>(range)
+    }
 }
 
 /// Function to read all the Shapes in a file.
@@ -1,6 +1,6 @@
 ///! Bounding Boxes
 use record::traits::{GrowablePoint, HasM, HasXY, HasZ, ShrinkablePoint};
-use record::EsriShape;
+use record::{EsriShape, ShapeType};
 use writer::{f64_max, f64_min};
 use PointZ;
 
@@ -77,6 +77,29 @@ impl<PointType: HasXY> GenericBBox<PointType> {
     pub fn y_range(&self) -> [f64; 2] {
         [self.min.y(), self.max.y()]
     }
+
+    /// Returns true if the two bboxes overlap in the x and y dimensions
+    ///
+    /// Bboxes that only touch each other are considered overlapping.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use shapefile::{Point, PointZ};
+    /// use shapefile::record::GenericBBox;
+    /// let bbox = GenericBBox { min: Point::new(0.0, 0.0), max: Point::new(1.0, 1.0) };
+    /// let other = GenericBBox { min: PointZ::new(1.0, 0.5, 7.0, 0.0), max: PointZ::new(2.0, 2.0, 7.0, 0.0) };
+    /// assert!(bbox.overlaps(&other));
+    ///
+    /// let other = GenericBBox { min: Point::new(1.5, 0.0), max: Point::new(2.0, 1.0) };
+    /// assert!(!bbox.overlaps(&other));
+    /// ```
+    pub fn overlaps<OtherPointType: HasXY>(&self, other: &GenericBBox<OtherPointType>) -> bool {
+        self.min.x() <= other.max.x()
+            && other.min.x() <= self.max.x()
+            && self.min.y() <= other.max.y()
+            && other.min.y() <= self.max.y()
+    }
 }
 
 impl<PointType: HasZ> GenericBBox<PointType> {
@@ -103,29 +126,51 @@ impl<PointType: Default> Default for GenericBBox<PointType> {
 pub type BBoxZ = GenericBBox<PointZ>;
 
 impl BBoxZ {
+    /// Creates the bbox of the shapes that are not Null shapes
+    ///
+    /// Like the writers that write one shape at a time, the bbox is all zeros
+    /// when there are only Null shapes.
     pub(crate) fn from_shapes<S: EsriShape>(shapes: &[S]) -> Self {
-        use std::f64::{MAX, MIN};
-        let mut bbox = Self {
-            max: PointZ::new(MIN, MIN, MIN, MIN),
-            min: PointZ::new(MAX, MAX, MAX, MAX),
+        let mut non_null_shapes = shapes
+            .iter()
+            .filter(|shape| shape.record_shapetype() != ShapeType::NullShape);
+        let mut bbox = match non_null_shapes.next() {
+            Some(shape) => Self::from_shape(shape),
+            None => return Self::default(),
         };
-
-        for shape in shapes {
-            let x_range = shape.x_range();
-            let y_range = shape.y_range();
-            let z_range = shape.z_range();
-            let m_range = shape.m_range();
-
-            bbox.min.x = f64_min(x_range[0], bbox.min.x);
-            bbox.min.y = f64_min(y_range[0], bbox.min.y);
-            bbox.min.z = f64_min(z_range[0], bbox.min.z);
-            bbox.min.m = f64_min(m_range[0], bbox.min.m);
-
-            bbox.max.x = f64_max(x_range[1], bbox.max.x);
-            bbox.max.y = f64_max(y_range[1], bbox.max.y);
-            bbox.max.z = f64_max(z_range[1], bbox.max.z);
-            bbox.max.m = f64_max(m_range[1], bbox.max.m);
+        for shape in non_null_shapes {
+            bbox.grow_from_shape(shape);
         }
         bbox
     }
+
+    /// Creates the bbox that contains exactly the given shape
+    pub(crate) fn from_shape<S: EsriShape>(shape: &S) -> Self {
+        let x_range = shape.x_range();
+        let y_range = shape.y_range();
+        let z_range = shape.z_range();
+        let m_range = shape.m_range();
+        Self {
+            min: PointZ::new(x_range[0], y_range[0], z_range[0], m_range[0]),
+            max: PointZ::new(x_range[1], y_range[1], z_range[1], m_range[1]),
+        }
+    }
+
+    /// Grows the bbox so that it also contains the given shape
+    pub(crate) fn grow_from_shape<S: EsriShape>(&mut self, shape: &S) {
+        let x_range = shape.x_range();
+        let y_range = shape.y_range();
+        let z_range = shape.z_range();
+        let m_range = shape.m_range();
+
+        self.min.x = f64_min(x_range[0], self.min.x);
+        self.min.y = f64_min(y_range[0], self.min.y);
+        self.min.z = f64_min(z_range[0], self.min.z);
+        self.min.m = f64_min(m_range[0], self.min.m);
+
+        self.max.x = f64_max(x_range[1], self.max.x);
+        self.max.y = f64_max(y_range[1], self.max.y);
+        self.max.z = f64_max(z_range[1], self.max.z);
+        self.max.m = f64_max(m_range[1], self.max.m);
+    }
 }
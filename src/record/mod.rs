@@ -11,6 +11,7 @@ pub mod point;
 pub mod polygon;
 pub mod polyline;
 pub mod traits;
+pub mod view;
 pub mod macros;
 
 use super::{Error, ShapeType};
@@ -68,6 +69,40 @@ impl<S: ConcreteReadableShape> ReadableShape for S {
     }
 }
 
+/// Reading as `Option<S>` accepts the Null shapes, that are allowed in any shapefile,
+/// they are read as `None`
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), shapefile::Error> {
+/// let polylines = shapefile::read_as::<_, Option<shapefile::Polyline>>("tests/data/line.shp")?;
+/// for polyline in polylines.iter().flatten() {
+///     println!("{}", polyline);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+impl<S: ConcreteReadableShape> ReadableShape for Option<S> {
+    fn read_from<T: Read>(mut source: &mut T, mut record_size: i32) -> Result<Self, Error> {
+        let shapetype = ShapeType::read_from(&mut source)?;
+        record_size -= std::mem::size_of::<i32>() as i32;
+        if shapetype == S::shapetype() {
+            S::read_shape_content(&mut source, record_size).map(Some)
+        } else if shapetype == ShapeType::NullShape {
+            // Some writers keep the size of the deleted shape in the header of the record
+            let remaining = record_size.max(0) as u64;
+            std::io::copy(&mut source.take(remaining), &mut std::io::sink())?;
+            Ok(None)
+        } else {
+            Err(Error::MismatchShapeType {
+                requested: S::shapetype(),
+                actual: shapetype,
+            })
+        }
+    }
+}
+
 /// Trait implemented by all Shapes that can be written
 pub trait WritableShape {
     /// Returns the size in bytes that the Shapes will take once written.
@@ -89,6 +124,70 @@ pub trait EsriShape: HasShapeType + WritableShape {
     fn m_range(&self) -> [f64; 2] {
         [0.0, 0.0]
     }
+    /// Returns the shape type written in the record of this shape
+    ///
+    /// Records of type `ShapeType::NullShape` have no content,
+    /// their shape is not part of the bbox of the file.
+    fn record_shapetype(&self) -> ShapeType {
+        Self::shapetype()
+    }
+}
+
+impl<S: HasShapeType> HasShapeType for Option<S> {
+    fn shapetype() -> ShapeType {
+        S::shapetype()
+    }
+}
+
+impl<S: WritableShape> WritableShape for Option<S> {
+    fn size_in_bytes(&self) -> usize {
+        self.as_ref().map_or(0, S::size_in_bytes)
+    }
+
+    fn write_to<T: Write>(&self, dest: &mut T) -> Result<(), Error> {
+        match self {
+            Some(shape) => shape.write_to(dest),
+            None => Ok(()),
+        }
+    }
+}
+
+/// `None` is written as a Null shape
+///
+/// # Example
+///
+/// ```
+/// use shapefile::{Point, Polyline};
+/// let polylines = vec![
+///     Some(Polyline::new(vec![Point::new(1.0, 1.0), Point::new(2.0, 2.0)])),
+///     None,
+/// ];
+/// let mut writer = shapefile::Writer::from_path("nullable_polylines.shp").unwrap();
+/// writer.write_shapes(&polylines).unwrap();
+/// ```
+impl<S: EsriShape> EsriShape for Option<S> {
+    fn x_range(&self) -> [f64; 2] {
+        self.as_ref().map_or([0.0, 0.0], S::x_range)
+    }
+
+    fn y_range(&self) -> [f64; 2] {
+        self.as_ref().map_or([0.0, 0.0], S::y_range)
+    }
+
+    fn z_range(&self) -> [f64; 2] {
+        self.as_ref().map_or([0.0, 0.0], S::z_range)
+    }
+
+    fn m_range(&self) -> [f64; 2] {
+        self.as_ref().map_or([0.0, 0.0], S::m_range)
+    }
+
+    fn record_shapetype(&self) -> ShapeType {
+        match self {
+            Some(shape) => shape.record_shapetype(),
+            None => ShapeType::NullShape,
+        }
+    }
 }
 
 pub(crate) fn is_part_closed<PointType: PartialEq>(points: &[PointType]) -> bool {
@@ -163,9 +262,79 @@ pub enum Shape {
     Multipatch(Multipatch),
 }
 
+/// The type of each shape is only known at runtime (see [Shape::shapetype](#method.shapetype)),
+/// `ShapeType::NullShape` is the type of a file where all the shapes are Null shapes
 impl HasShapeType for Shape {
     fn shapetype() -> ShapeType {
-        ShapeType::Point
+        ShapeType::NullShape
+    }
+}
+
+/// Evaluates `$e` with `$inner` bound to the shape held by the variant,
+/// or evaluates `$null` for the `NullShape`
+macro_rules! match_inner_shape {
+    ($shape:expr, $inner:ident => $e:expr, NullShape => $null:expr) => {
+        match $shape {
+            Shape::Polyline($inner) => $e,
+            Shape::PolylineM($inner) => $e,
+            Shape::PolylineZ($inner) => $e,
+            Shape::Point($inner) => $e,
+            Shape::PointM($inner) => $e,
+            Shape::PointZ($inner) => $e,
+            Shape::Polygon($inner) => $e,
+            Shape::PolygonM($inner) => $e,
+            Shape::PolygonZ($inner) => $e,
+            Shape::Multipoint($inner) => $e,
+            Shape::MultipointM($inner) => $e,
+            Shape::MultipointZ($inner) => $e,
+            Shape::Multipatch($inner) => $e,
+            Shape::NullShape => $null,
+        }
+    };
+}
+
+impl WritableShape for Shape {
+    fn size_in_bytes(&self) -> usize {
+        match_inner_shape!(self, shape => shape.size_in_bytes(), NullShape => 0)
+    }
+
+    fn write_to<T: Write>(&self, dest: &mut T) -> Result<(), Error> {
+        match_inner_shape!(self, shape => shape.write_to(dest), NullShape => Ok(()))
+    }
+}
+
+/// Shapes of different variants can be given to the writers, which return an error
+/// if the variants that are not `NullShape` do not all have the same type
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), shapefile::Error> {
+/// let shapes = shapefile::read("tests/data/line.shp")?;
+/// let mut writer = shapefile::Writer::from_path("lines_copy.shp")?;
+/// writer.write_shapes(&shapes)?;
+/// # Ok(())
+/// # }
+/// ```
+impl EsriShape for Shape {
+    fn x_range(&self) -> [f64; 2] {
+        match_inner_shape!(self, shape => shape.x_range(), NullShape => [0.0, 0.0])
+    }
+
+    fn y_range(&self) -> [f64; 2] {
+        match_inner_shape!(self, shape => shape.y_range(), NullShape => [0.0, 0.0])
+    }
+
+    fn z_range(&self) -> [f64; 2] {
+        match_inner_shape!(self, shape => shape.z_range(), NullShape => [0.0, 0.0])
+    }
+
+    fn m_range(&self) -> [f64; 2] {
+        match_inner_shape!(self, shape => shape.m_range(), NullShape => [0.0, 0.0])
+    }
+
+    fn record_shapetype(&self) -> ShapeType {
+        self.shapetype()
     }
 }
 
@@ -231,8 +400,8 @@ impl Shape {
             Shape::PolygonM(_) => ShapeType::PolygonM,
             Shape::PolygonZ(_) => ShapeType::PolygonZ,
             Shape::Multipoint(_) => ShapeType::Multipoint,
-            Shape::MultipointM(_) => ShapeType::Multipoint,
-            Shape::MultipointZ(_) => ShapeType::Multipoint,
+            Shape::MultipointM(_) => ShapeType::MultipointM,
+            Shape::MultipointZ(_) => ShapeType::MultipointZ,
             Shape::Multipatch(_) => ShapeType::Multipatch,
             Shape::NullShape => ShapeType::NullShape,
         }
@@ -262,14 +431,21 @@ impl fmt::Display for Shape {
 }
 
 /// Header of a shape record, present before any shape record
-pub(crate) struct RecordHeader {
+///
+/// Both values are stored in big endian.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RecordHeader {
+    /// Number of the record, starting from 1
     pub record_number: i32,
+    /// Length of the content of the record (without this header), in 16-bit words
     pub record_size: i32,
 }
 
 impl RecordHeader {
-    pub(crate) const SIZE: usize = 2 * std::mem::size_of::<i32>();
+    /// Size of the header in bytes
+    pub const SIZE: usize = 2 * std::mem::size_of::<i32>();
 
+    /// Reads the header from the source
     pub fn read_from<T: Read>(source: &mut T) -> Result<RecordHeader, Error> {
         let record_number = source.read_i32::<BigEndian>()?;
         let record_size = source.read_i32::<BigEndian>()?;
@@ -279,6 +455,22 @@ impl RecordHeader {
         })
     }
 
+    /// Returns the length in bytes of the content of the record
+    ///
+    /// Fails with `Error::InvalidShapeRecordSize` if the length is negative,
+    /// or does not fit in an `i32` (the type shapes are read with).
+    pub(crate) fn content_length(&self) -> Result<usize, Error> {
+        if self.record_size < 0 {
+            return Err(Error::InvalidShapeRecordSize);
+        }
+        let length = self
+            .record_size
+            .checked_mul(2)
+            .ok_or(Error::InvalidShapeRecordSize)?;
+        Ok(length as usize)
+    }
+
+    /// Writes the header to the dest
     pub fn write_to<T: Write>(&self, dest: &mut T) -> Result<(), std::io::Error> {
         dest.write_i32::<BigEndian>(self.record_number)?;
         dest.write_i32::<BigEndian>(self.record_size)?;
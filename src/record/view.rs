@@ -0,0 +1,521 @@
+//! Borrowed views over the bytes of Polyline and Polygon records
+//!
+//! Reading a [Polyline](../polyline/type.Polyline.html) or a [Polygon](../polygon/type.Polygon.html)
+//! allocates a `Vec` for each of its parts.
+//! The views of this module instead wrap the bytes of the record and decode the points
+//! only when they are accessed, which is useful when the points are only read once.
+//!
+//! The bytes of the records can be obtained from a reader over an in-memory source with
+//! [iter_record_bytes](../../reader/struct.Reader.html#method.iter_record_bytes).
+//!
+//! # Example
+//!
+//! ```
+//! # use std::io::Cursor;
+//! use shapefile::record::view::PolylineView;
+//! let shp = std::fs::read("tests/data/line.shp").unwrap();
+//! let reader = shapefile::Reader::new(Cursor::new(shp)).unwrap();
+//! for bytes in reader.iter_record_bytes() {
+//!     let polyline = PolylineView::new(bytes.unwrap()).unwrap();
+//!     for part in polyline.parts() {
+//!         let sum_x = part.iter().map(|point| point.x).sum::<f64>();
+//!         println!("{} points, mean x: {}", part.len(), sum_x / part.len() as f64);
+//!     }
+//! }
+//! ```
+use std::marker::PhantomData;
+use std::mem::size_of;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use record::polygon::GenericPolygon;
+use record::polyline::GenericPolyline;
+use record::traits::HasXY;
+use record::{GenericBBox, HasShapeType, NO_DATA};
+use {Error, ShapeType};
+use {Point, PointM, PointZ};
+
+/// Size of the bbox, num parts and num points that start the record of a multi-part shape
+const MULTI_PART_HEADER_SIZE: usize = 4 * size_of::<f64>() + 2 * size_of::<i32>();
+/// Size of the range that precedes the z or m values
+const RANGE_SIZE: usize = 2 * size_of::<f64>();
+
+/// Trait implemented by the points that can be decoded from the bytes of a record
+pub trait ViewablePoint: Copy {
+    /// Returns whether the record stores a z value for each point
+    fn has_z() -> bool;
+    /// Returns whether the record may store a m value for each point
+    fn has_m() -> bool;
+    /// Creates the point from its values, `z` and `m` are ignored if the point does not have them
+    fn from_xyzm(x: f64, y: f64, z: f64, m: f64) -> Self;
+}
+
+impl ViewablePoint for Point {
+    fn has_z() -> bool {
+        false
+    }
+
+    fn has_m() -> bool {
+        false
+    }
+
+    fn from_xyzm(x: f64, y: f64, _z: f64, _m: f64) -> Self {
+        Point { x, y }
+    }
+}
+
+impl ViewablePoint for PointM {
+    fn has_z() -> bool {
+        false
+    }
+
+    fn has_m() -> bool {
+        true
+    }
+
+    fn from_xyzm(x: f64, y: f64, _z: f64, m: f64) -> Self {
+        PointM { x, y, m }
+    }
+}
+
+impl ViewablePoint for PointZ {
+    fn has_z() -> bool {
+        true
+    }
+
+    fn has_m() -> bool {
+        true
+    }
+
+    fn from_xyzm(x: f64, y: f64, z: f64, m: f64) -> Self {
+        PointZ { x, y, z, m }
+    }
+}
+
+/// Location of the different arrays inside the bytes of a multi-part record
+#[derive(Copy, Clone)]
+struct MultiPartView<'a, PointType> {
+    /// The record content, without the shape type
+    bytes: &'a [u8],
+    num_parts: usize,
+    num_points: usize,
+    /// Offset of the z range, followed by the z values
+    z_offset: Option<usize>,
+    /// Offset of the m range, followed by the m values
+    m_offset: Option<usize>,
+    _point: PhantomData<PointType>,
+}
+
+impl<'a, PointType: ViewablePoint> MultiPartView<'a, PointType> {
+    fn new(record: &'a [u8], expected_shapetype: ShapeType) -> Result<Self, Error> {
+        if record.len() < size_of::<i32>() + MULTI_PART_HEADER_SIZE {
+            return Err(Error::InvalidShapeRecordSize);
+        }
+        let shapetype = ShapeType::read_from(&mut &record[..size_of::<i32>()])?;
+        if shapetype != expected_shapetype {
+            return Err(Error::MismatchShapeType {
+                requested: expected_shapetype,
+                actual: shapetype,
+            });
+        }
+
+        let bytes = &record[size_of::<i32>()..];
+        let num_parts = LittleEndian::read_i32(&bytes[32..36]);
+        let num_points = LittleEndian::read_i32(&bytes[36..40]);
+        if num_parts < 0 || num_points < 0 {
+            return Err(Error::InvalidShapeRecordSize);
+        }
+        let (num_parts, num_points) = (num_parts as usize, num_points as usize);
+
+        let mut size = MULTI_PART_HEADER_SIZE
+            + num_parts * size_of::<i32>()
+            + num_points * 2 * size_of::<f64>();
+        let values_size = RANGE_SIZE + num_points * size_of::<f64>();
+        let z_offset = if PointType::has_z() {
+            size += values_size;
+            Some(size - values_size)
+        } else {
+            None
+        };
+
+        let m_offset = if bytes.len() == size {
+            None
+        } else if PointType::has_m() && bytes.len() == size + values_size {
+            Some(size)
+        } else {
+            return Err(Error::InvalidShapeRecordSize);
+        };
+
+        Ok(Self {
+            bytes,
+            num_parts,
+            num_points,
+            z_offset,
+            m_offset,
+            _point: PhantomData,
+        })
+    }
+
+    fn f64_at(&self, offset: usize) -> f64 {
+        LittleEndian::read_f64(&self.bytes[offset..offset + size_of::<f64>()])
+    }
+
+    fn bbox(&self) -> GenericBBox<PointType> {
+        let (min_z, max_z) = match self.z_offset {
+            Some(offset) => (self.f64_at(offset), self.f64_at(offset + size_of::<f64>())),
+            None => (0.0, 0.0),
+        };
+        let (min_m, max_m) = match self.m_offset {
+            Some(offset) => (self.f64_at(offset), self.f64_at(offset + size_of::<f64>())),
+            None => (NO_DATA, NO_DATA),
+        };
+        GenericBBox {
+            min: PointType::from_xyzm(self.f64_at(0), self.f64_at(8), min_z, min_m),
+            max: PointType::from_xyzm(self.f64_at(16), self.f64_at(24), max_z, max_m),
+        }
+    }
+
+    /// Index of the first point of the part, clamped to the number of points
+    fn part_start(&self, index: usize) -> usize {
+        if index >= self.num_parts {
+            return self.num_points;
+        }
+        let offset = MULTI_PART_HEADER_SIZE + index * size_of::<i32>();
+        let start = LittleEndian::read_i32(&self.bytes[offset..offset + size_of::<i32>()]);
+        (start.max(0) as usize).min(self.num_points)
+    }
+
+    fn part(&self, index: usize) -> Option<PointsView<'a, PointType>> {
+        if index >= self.num_parts {
+            return None;
+        }
+        let start = self.part_start(index);
+        let end = self.part_start(index + 1).max(start);
+        Some(PointsView {
+            multi_part: *self,
+            start,
+            end,
+        })
+    }
+
+    fn point(&self, index: usize) -> PointType {
+        let xy_offset = MULTI_PART_HEADER_SIZE
+            + self.num_parts * size_of::<i32>()
+            + index * 2 * size_of::<f64>();
+        let z = match self.z_offset {
+            Some(offset) => self.f64_at(offset + RANGE_SIZE + index * size_of::<f64>()),
+            None => 0.0,
+        };
+        let m = match self.m_offset {
+            Some(offset) => f64::max(
+                self.f64_at(offset + RANGE_SIZE + index * size_of::<f64>()),
+                NO_DATA,
+            ),
+            None => NO_DATA,
+        };
+        PointType::from_xyzm(
+            self.f64_at(xy_offset),
+            self.f64_at(xy_offset + size_of::<f64>()),
+            z,
+            m,
+        )
+    }
+}
+
+/// A contiguous range of points of a record (a part of a polyline, a ring of a polygon)
+///
+/// The points are decoded each time they are accessed.
+#[derive(Copy, Clone)]
+pub struct PointsView<'a, PointType> {
+    multi_part: MultiPartView<'a, PointType>,
+    start: usize,
+    end: usize,
+}
+
+impl<'a, PointType: ViewablePoint> PointsView<'a, PointType> {
+    /// Returns the number of points
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Returns true if there are no points
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Returns the point at `index`, `None` if the index is out of range
+    pub fn get(&self, index: usize) -> Option<PointType> {
+        if index < self.len() {
+            Some(self.multi_part.point(self.start + index))
+        } else {
+            None
+        }
+    }
+
+    /// Returns an iterator over the points
+    pub fn iter(&self) -> PointsIter<'a, PointType> {
+        PointsIter { view: *self }
+    }
+
+    /// Copies the points into a `Vec`
+    pub fn to_vec(&self) -> Vec<PointType> {
+        self.iter().collect()
+    }
+}
+
+impl<'a, PointType: ViewablePoint> IntoIterator for PointsView<'a, PointType> {
+    type Item = PointType;
+    type IntoIter = PointsIter<'a, PointType>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        PointsIter { view: self }
+    }
+}
+
+/// Iterator over the points of a [PointsView](struct.PointsView.html)
+pub struct PointsIter<'a, PointType> {
+    view: PointsView<'a, PointType>,
+}
+
+impl<'a, PointType: ViewablePoint> Iterator for PointsIter<'a, PointType> {
+    type Item = PointType;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.view.is_empty() {
+            None
+        } else {
+            let point = self.view.multi_part.point(self.view.start);
+            self.view.start += 1;
+            Some(point)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.view.len(), Some(self.view.len()))
+    }
+}
+
+impl<'a, PointType: ViewablePoint> ExactSizeIterator for PointsIter<'a, PointType> {}
+
+/// Iterator over the parts of a record
+pub struct PartsIter<'a, PointType> {
+    multi_part: MultiPartView<'a, PointType>,
+    current_part: usize,
+}
+
+impl<'a, PointType: ViewablePoint> Iterator for PartsIter<'a, PointType> {
+    type Item = PointsView<'a, PointType>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let part = self.multi_part.part(self.current_part)?;
+        self.current_part += 1;
+        Some(part)
+    }
+}
+
+/// Generic struct to view the bytes of a Polyline, PolylineM or PolylineZ record
+///
+/// This is the borrowed counterpart of [GenericPolyline](../polyline/struct.GenericPolyline.html)
+#[derive(Copy, Clone)]
+pub struct GenericPolylineView<'a, PointType> {
+    multi_part: MultiPartView<'a, PointType>,
+}
+
+impl<'a, PointType> GenericPolylineView<'a, PointType>
+where
+    PointType: ViewablePoint,
+    GenericPolyline<PointType>: HasShapeType,
+{
+    /// Creates a view over the bytes of a record
+    ///
+    /// The `record` starts with the shape type (the record header is not included)
+    ///
+    /// # Errors
+    ///
+    /// `Error::MismatchShapeType` if the record is not of the polyline type
+    /// corresponding to `PointType`,
+    /// `Error::InvalidShapeRecordSize` if the size of the record does not match its number of points
+    pub fn new(record: &'a [u8]) -> Result<Self, Error> {
+        let multi_part = MultiPartView::new(record, GenericPolyline::<PointType>::shapetype())?;
+        Ok(Self { multi_part })
+    }
+}
+
+impl<'a, PointType: ViewablePoint> GenericPolylineView<'a, PointType> {
+    /// Returns the bounding box of the polyline, as written in the record
+    pub fn bbox(&self) -> GenericBBox<PointType> {
+        self.multi_part.bbox()
+    }
+
+    /// Returns the number of parts
+    pub fn num_parts(&self) -> usize {
+        self.multi_part.num_parts
+    }
+
+    /// Returns the number of points, all parts included
+    pub fn total_point_count(&self) -> usize {
+        self.multi_part.num_points
+    }
+
+    /// Returns the index of the first point of each part
+    pub fn part_offsets(&self) -> Vec<usize> {
+        (0..self.num_parts())
+            .map(|i| self.multi_part.part_start(i))
+            .collect()
+    }
+
+    /// Returns the part at `index`
+    pub fn part(&self, index: usize) -> Option<PointsView<'a, PointType>> {
+        self.multi_part.part(index)
+    }
+
+    /// Returns an iterator over the parts
+    pub fn parts(&self) -> PartsIter<'a, PointType> {
+        PartsIter {
+            multi_part: self.multi_part,
+            current_part: 0,
+        }
+    }
+
+    /// Decodes the points and returns the owned polyline
+    pub fn to_polyline(&self) -> GenericPolyline<PointType> {
+        GenericPolyline {
+            bbox: self.bbox(),
+            parts: self.parts().map(|part| part.to_vec()).collect(),
+        }
+    }
+}
+
+/// View over the bytes of a [Polyline](../polyline/type.Polyline.html) record
+pub type PolylineView<'a> = GenericPolylineView<'a, Point>;
+/// View over the bytes of a [PolylineM](../polyline/type.PolylineM.html) record
+pub type PolylineMView<'a> = GenericPolylineView<'a, PointM>;
+/// View over the bytes of a [PolylineZ](../polyline/type.PolylineZ.html) record
+pub type PolylineZView<'a> = GenericPolylineView<'a, PointZ>;
+
+/// View over a ring of a polygon record
+///
+/// Like [PolygonRing](../polygon/enum.PolygonRing.html), the type of ring is
+/// given by the ordering of its points.
+#[derive(Copy, Clone)]
+pub enum PolygonRingView<'a, PointType> {
+    /// The outer ring of a polygon.
+    Outer(PointsView<'a, PointType>),
+    /// Defines a hole in a polygon
+    Inner(PointsView<'a, PointType>),
+}
+
+impl<'a, PointType> PolygonRingView<'a, PointType> {
+    /// Returns the points of the ring
+    pub fn points(&self) -> &PointsView<'a, PointType> {
+        match self {
+            PolygonRingView::Outer(points) => points,
+            PolygonRingView::Inner(points) => points,
+        }
+    }
+}
+
+/// Iterator over the rings of a polygon record
+pub struct RingsIter<'a, PointType> {
+    parts: PartsIter<'a, PointType>,
+}
+
+impl<'a, PointType: ViewablePoint + HasXY> Iterator for RingsIter<'a, PointType> {
+    type Item = PolygonRingView<'a, PointType>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.parts.next().map(ring_view_of)
+    }
+}
+
+fn ring_view_of<'a, PointType: ViewablePoint + HasXY>(
+    points: PointsView<'a, PointType>,
+) -> PolygonRingView<'a, PointType> {
+    // Same computation as ring_type_from_points_ordering, without collecting the points
+    let area = points
+        .iter()
+        .zip(points.iter().skip(1))
+        .map(|(p0, p1)| (p1.x() - p0.x()) * (p1.y() + p0.y()))
+        .sum::<f64>();
+    if area < 0.0 {
+        PolygonRingView::Inner(points)
+    } else {
+        PolygonRingView::Outer(points)
+    }
+}
+
+/// Generic struct to view the bytes of a Polygon, PolygonM or PolygonZ record
+///
+/// This is the borrowed counterpart of [GenericPolygon](../polygon/struct.GenericPolygon.html)
+#[derive(Copy, Clone)]
+pub struct GenericPolygonView<'a, PointType> {
+    multi_part: MultiPartView<'a, PointType>,
+}
+
+impl<'a, PointType> GenericPolygonView<'a, PointType>
+where
+    PointType: ViewablePoint,
+    GenericPolygon<PointType>: HasShapeType,
+{
+    /// Creates a view over the bytes of a record
+    ///
+    /// The `record` starts with the shape type (the record header is not included)
+    ///
+    /// # Errors
+    ///
+    /// `Error::MismatchShapeType` if the record is not of the polygon type
+    /// corresponding to `PointType`,
+    /// `Error::InvalidShapeRecordSize` if the size of the record does not match its number of points
+    pub fn new(record: &'a [u8]) -> Result<Self, Error> {
+        let multi_part = MultiPartView::new(record, GenericPolygon::<PointType>::shapetype())?;
+        Ok(Self { multi_part })
+    }
+}
+
+impl<'a, PointType: ViewablePoint + HasXY> GenericPolygonView<'a, PointType> {
+    /// Returns the bounding box of the polygon, as written in the record
+    pub fn bbox(&self) -> GenericBBox<PointType> {
+        self.multi_part.bbox()
+    }
+
+    /// Returns the number of rings
+    pub fn num_rings(&self) -> usize {
+        self.multi_part.num_parts
+    }
+
+    /// Returns the number of points, all rings included
+    pub fn total_point_count(&self) -> usize {
+        self.multi_part.num_points
+    }
+
+    /// Returns the index of the first point of each ring
+    pub fn ring_offsets(&self) -> Vec<usize> {
+        (0..self.num_rings())
+            .map(|i| self.multi_part.part_start(i))
+            .collect()
+    }
+
+    /// Returns the ring at `index`
+    pub fn ring(&self, index: usize) -> Option<PolygonRingView<'a, PointType>> {
+        self.multi_part.part(index).map(ring_view_of)
+    }
+
+    /// Returns an iterator over the rings
+    ///
+    /// To know if a ring is an outer or inner one, its points are decoded once
+    pub fn rings(&self) -> RingsIter<'a, PointType> {
+        RingsIter {
+            parts: PartsIter {
+                multi_part: self.multi_part,
+                current_part: 0,
+            },
+        }
+    }
+}
+
+/// View over the bytes of a [Polygon](../polygon/type.Polygon.html) record
+pub type PolygonView<'a> = GenericPolygonView<'a, Point>;
+/// View over the bytes of a [PolygonM](../polygon/type.PolygonM.html) record
+pub type PolygonMView<'a> = GenericPolygonView<'a, PointM>;
+/// View over the bytes of a [PolygonZ](../polygon/type.PolygonZ.html) record
+pub type PolygonZView<'a> = GenericPolygonView<'a, PointZ>;
@@ -0,0 +1,74 @@
+//! Module with the types used to build an in-memory R-tree of the shapes of a shapefile
+//!
+//! Requires the `rstar` feature.
+//!
+//! The tree is built by [Reader::build_rtree](../reader/struct.Reader.html#method.build_rtree),
+//! it only stores the bbox of the shapes and their index, which can then be given to
+//! [Reader::read_nth_shape](../reader/struct.Reader.html#method.read_nth_shape)
+//! to read the shapes found by the queries.
+//!
+//! # Example
+//!
+//! ```
+//! use shapefile::record::GenericBBox;
+//! use shapefile::rtree::envelope_of;
+//! use shapefile::Point;
+//!
+//! let mut reader = shapefile::Reader::from_path("tests/data/line.shp").unwrap();
+//! let tree = reader.build_rtree().unwrap();
+//!
+//! let bbox = GenericBBox { min: Point::new(0.0, 0.0), max: Point::new(2.0, 2.0) };
+//! for shape_envelope in tree.locate_in_envelope_intersecting(&envelope_of(&bbox)) {
+//!     let shape = reader.read_nth_shape(shape_envelope.index()).unwrap().unwrap();
+//! }
+//!
+//! let nearest = tree.nearest_neighbor(&[0.0, 0.0]).unwrap();
+//! assert_eq!(nearest.index(), 0);
+//! ```
+use rstar::{Envelope, PointDistance, RTreeObject, AABB};
+
+use record::GenericBBox;
+use Point;
+
+/// Returns the rstar envelope corresponding to the xy bbox
+pub fn envelope_of(bbox: &GenericBBox<Point>) -> AABB<[f64; 2]> {
+    AABB::from_corners([bbox.min.x, bbox.min.y], [bbox.max.x, bbox.max.y])
+}
+
+/// The bbox of a shape and its index in the shapefile, as stored in the R-tree
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShapeEnvelope {
+    pub(crate) index: usize,
+    pub(crate) bbox: GenericBBox<Point>,
+}
+
+impl ShapeEnvelope {
+    /// Returns the index of the shape (starting from 0)
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the xy bbox of the shape
+    pub fn bbox(&self) -> &GenericBBox<Point> {
+        &self.bbox
+    }
+}
+
+impl RTreeObject for ShapeEnvelope {
+    type Envelope = AABB<[f64; 2]>;
+
+    fn envelope(&self) -> Self::Envelope {
+        envelope_of(&self.bbox)
+    }
+}
+
+/// The distance is the one to the bbox of the shape, 0 if the point is inside
+impl PointDistance for ShapeEnvelope {
+    fn distance_2(&self, point: &[f64; 2]) -> f64 {
+        self.envelope().distance_2(point)
+    }
+
+    fn contains_point(&self, point: &[f64; 2]) -> bool {
+        self.envelope().contains_point(point)
+    }
+}
@@ -0,0 +1,426 @@
+//! Module to read the .sbn spatial index that ESRI software writes next to shapefiles
+//!
+//! The .sbn format is not documented by ESRI, this implementation follows the one of
+//! [shapelib](http://shapelib.maptools.org/) (`sbnsearch.c`).
+//!
+//! The index is a binary tree over the extent of the shapefile, each level splitting
+//! the space of its parent in two, alternatively along the x and y axis.
+//! Each node lists the shapes that fit in it but not in one of its children,
+//! with their bbox stored in a [0, 255] integer coordinate space.
+//!
+//! The nodes are stored in *bins* of at most 100 shapes, the .sbx file only contains
+//! the offsets of the bins in the .sbn, as the whole .sbn is read by
+//! [SpatialIndex::read_from](struct.SpatialIndex.html#method.read_from),
+//! the .sbx is not needed.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use shapefile::record::GenericBBox;
+//! use shapefile::sbn::SpatialIndex;
+//! use shapefile::Point;
+//!
+//! let index = SpatialIndex::from_path("states.sbn").unwrap();
+//! let bbox = GenericBBox { min: Point::new(-80.0, 35.0), max: Point::new(-75.0, 40.0) };
+//! for shape_index in index.query(&bbox) {
+//!     println!("The shape n°{} may intersect the bbox", shape_index);
+//! }
+//! ```
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use record::GenericBBox;
+use {Error, Point};
+
+/// Size of the header, which is followed by the header of the node descriptors bin
+const SBN_HEADER_SIZE: usize = 100;
+/// Max depth of the tree
+const MAX_DEPTH: u32 = 24;
+/// Max number of shapes in a bin
+const MAX_SHAPES_PER_BIN: usize = 100;
+/// Size of the description of a shape in a bin
+const SHAPE_DESC_SIZE: usize = 8;
+
+fn invalid(msg: &str) -> Error {
+    Error::InvalidSpatialIndex(msg.to_string())
+}
+
+/// A shape as stored in a node, its bbox is in the [0, 255] coordinate space
+#[derive(Debug, Copy, Clone)]
+struct ShapeDesc {
+    min: [u8; 2],
+    max: [u8; 2],
+    /// 1-based index of the shape
+    id: i32,
+}
+
+/// Bbox in the [0, 255] coordinate space
+#[derive(Debug, Copy, Clone)]
+struct ByteBBox {
+    min: [u8; 2],
+    max: [u8; 2],
+}
+
+impl ByteBBox {
+    /// Rounding of the integer coordinates allows a strict intersection test,
+    /// except when one of the bbox is degenerated
+    fn intersects(&self, min: [u8; 2], max: [u8; 2]) -> bool {
+        (0..2).all(|axis| {
+            (self.min[axis] < max[axis] && self.max[axis] > min[axis])
+                || ((min[axis] == max[axis] || self.min[axis] == self.max[axis])
+                    && self.min[axis] <= max[axis]
+                    && self.max[axis] >= min[axis])
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    shapes: Vec<ShapeDesc>,
+    /// Bbox of the shapes of the node, `None` if the node is empty
+    bbox: Option<ByteBBox>,
+}
+
+/// The content of a .sbn file
+#[derive(Debug)]
+pub struct SpatialIndex {
+    extent: GenericBBox<Point>,
+    num_shapes: usize,
+    max_depth: u32,
+    nodes: Vec<Node>,
+}
+
+impl SpatialIndex {
+    /// Reads the whole .sbn from the source
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidSpatialIndex` if the content is not a valid .sbn
+    pub fn read_from<T: Read>(mut source: T) -> Result<Self, Error> {
+        let mut header = [0u8; SBN_HEADER_SIZE];
+        source.read_exact(&mut header)?;
+        if header[..3] != [0x00, 0x00, 0x27]
+            || (header[3] != 0x0A && header[3] != 0x0D)
+            || header[4..8] != [0xFF, 0xFF, 0xFE, 0x70]
+        {
+            return Err(invalid("invalid file code"));
+        }
+
+        let mut values = &header[24..64];
+        // In 16-bit words
+        let file_size = values.read_i32::<BigEndian>()?.max(0) as usize * 2;
+        let num_shapes = values.read_i32::<BigEndian>()?;
+        let mut extent = GenericBBox::<Point>::default();
+        extent.min.x = values.read_f64::<BigEndian>()?;
+        extent.min.y = values.read_f64::<BigEndian>()?;
+        extent.max.x = values.read_f64::<BigEndian>()?;
+        extent.max.y = values.read_f64::<BigEndian>()?;
+        if extent.min.x > extent.max.x || extent.min.y > extent.max.y {
+            return Err(invalid("invalid extent"));
+        }
+        // Each shape is described in a bin, after the header and the bin of the node descriptors
+        let max_shapes = file_size.saturating_sub(SBN_HEADER_SIZE + 8) / SHAPE_DESC_SIZE;
+        if num_shapes < 0 || num_shapes as usize > max_shapes {
+            return Err(invalid("invalid number of shapes"));
+        }
+        let num_shapes = num_shapes as usize;
+
+        let mut max_depth = 2;
+        while max_depth < MAX_DEPTH && num_shapes > ((1 << max_depth) - 1) * 8 {
+            max_depth += 1;
+        }
+        let max_nodes = (1usize << max_depth) - 1;
+        let mut index = Self {
+            extent,
+            num_shapes,
+            max_depth,
+            nodes: Vec::new(),
+        };
+        if num_shapes == 0 {
+            return Ok(index);
+        }
+
+        // The node descriptors are stored in the first bin
+        if source.read_i32::<BigEndian>()? != 1 {
+            return Err(invalid("unexpected id for the first bin"));
+        }
+        let descriptors_size = source.read_i32::<BigEndian>()?;
+        let num_descriptors = (descriptors_size / 4) as usize;
+        if descriptors_size < 0 || descriptors_size % 4 != 0 || num_descriptors > max_nodes {
+            return Err(invalid("invalid size of node descriptors"));
+        }
+
+        // (bin of the first shapes of the node, node id, number of shapes)
+        let mut node_starts = Vec::<(i32, usize, usize)>::new();
+        for node_id in 0..num_descriptors {
+            let bin_start = source.read_i32::<BigEndian>()?;
+            let shape_count = source.read_i32::<BigEndian>()?;
+            // The nodes are only allocated as their descriptors are read
+            index.nodes.push(Node::default());
+            if (bin_start > 0 && shape_count == 0)
+                || shape_count < 0
+                || shape_count as usize > num_shapes
+            {
+                return Err(invalid("inconsistent number of shapes in a node"));
+            }
+            if bin_start > 0 {
+                node_starts.push((bin_start, node_id, shape_count as usize));
+            }
+        }
+        node_starts.sort_by_key(|&(bin_start, _, _)| bin_start);
+        if node_starts.first().map(|start| start.0) != Some(2) {
+            return Err(invalid("the first node does not start at the second bin"));
+        }
+        if node_starts.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return Err(invalid("two nodes start at the same bin"));
+        }
+
+        let mut bin_header = [0u8; 8];
+        let mut expected_bin_id = 1;
+        let mut current_node = 0;
+        while read_exact_or_eof(&mut source, &mut bin_header)? {
+            expected_bin_id += 1;
+            let mut bin_header = &bin_header[..];
+            let bin_id = bin_header.read_i32::<BigEndian>()?;
+            let bin_size = bin_header.read_i32::<BigEndian>()?;
+            let num_bin_shapes = (bin_size / 4) as usize;
+            if bin_id != expected_bin_id {
+                return Err(invalid("unexpected bin id"));
+            }
+            if bin_size <= 0 || bin_size % 4 != 0 || num_bin_shapes > MAX_SHAPES_PER_BIN {
+                return Err(invalid("unexpected bin size"));
+            }
+
+            if node_starts
+                .get(current_node + 1)
+                .is_some_and(|start| start.0 == bin_id)
+            {
+                current_node += 1;
+            }
+            let node = &mut index.nodes[node_starts[current_node].1];
+
+            let mut shape_desc = [0u8; SHAPE_DESC_SIZE];
+            for _ in 0..num_bin_shapes {
+                source.read_exact(&mut shape_desc)?;
+                let shape = ShapeDesc {
+                    min: [shape_desc[0], shape_desc[1]],
+                    max: [shape_desc[2], shape_desc[3]],
+                    id: (&shape_desc[4..]).read_i32::<BigEndian>()?,
+                };
+                node.bbox = Some(match node.bbox {
+                    None => ByteBBox {
+                        min: shape.min,
+                        max: shape.max,
+                    },
+                    Some(bbox) => ByteBBox {
+                        min: [bbox.min[0].min(shape.min[0]), bbox.min[1].min(shape.min[1])],
+                        max: [bbox.max[0].max(shape.max[0]), bbox.max[1].max(shape.max[1])],
+                    },
+                });
+                node.shapes.push(shape);
+            }
+        }
+
+        if current_node + 1 != node_starts.len() {
+            return Err(invalid("missing bins"));
+        }
+        for &(_, node_id, shape_count) in &node_starts {
+            if index.nodes[node_id].shapes.len() != shape_count {
+                return Err(invalid("inconsistent number of shapes in a node"));
+            }
+        }
+        Ok(index)
+    }
+
+    /// Reads the .sbn file at the given path
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Returns the extent of the shapes, as written in the header
+    pub fn extent(&self) -> &GenericBBox<Point> {
+        &self.extent
+    }
+
+    /// Returns the number of shapes indexed, as written in the header
+    pub fn num_shapes(&self) -> usize {
+        self.num_shapes
+    }
+
+    /// Returns the indices (starting from 0) of the shapes whose bbox may intersect
+    /// the given bbox, in increasing order
+    ///
+    /// As the index stores bboxes with a reduced precision, the result may contain
+    /// shapes that are close to the bbox but do not intersect it.
+    pub fn query(&self, bbox: &GenericBBox<Point>) -> Vec<usize> {
+        let mut shape_indices = Vec::<usize>::new();
+        if self.nodes.is_empty() || !bbox.overlaps(&self.extent) {
+            return shape_indices;
+        }
+
+        let x_range = to_byte_range(bbox.x_range(), self.extent.x_range());
+        let y_range = to_byte_range(bbox.y_range(), self.extent.y_range());
+        let search = ByteBBox {
+            min: [x_range[0], y_range[0]],
+            max: [x_range[1], y_range[1]],
+        };
+        let root = ByteBBox {
+            min: [0, 0],
+            max: [255, 255],
+        };
+        self.search_node(&search, 0, 0, root, &mut shape_indices);
+        shape_indices.sort();
+        shape_indices
+    }
+
+    fn search_node(
+        &self,
+        search: &ByteBBox,
+        depth: u32,
+        node_id: usize,
+        node_bbox: ByteBBox,
+        shape_indices: &mut Vec<usize>,
+    ) {
+        // The nodes past the last descriptor, and their children, are empty
+        let node = match self.nodes.get(node_id) {
+            Some(node) => node,
+            None => return,
+        };
+        if let Some(ref bbox) = node.bbox {
+            if search.intersects(bbox.min, bbox.max) {
+                let hits = node
+                    .shapes
+                    .iter()
+                    .filter(|shape| search.intersects(shape.min, shape.max) && shape.id > 0);
+                shape_indices.extend(hits.map(|shape| (shape.id - 1) as usize));
+            }
+        }
+
+        if depth + 1 >= self.max_depth {
+            return;
+        }
+        // Even depths split along x, odd ones along y
+        let axis = (depth % 2) as usize;
+        let child_id = node_id * 2 + 1;
+        let mid = (1 + (u16::from(node_bbox.min[axis]) + u16::from(node_bbox.max[axis])) / 2) as u8;
+        if search.min[axis] < mid {
+            let mut lower = node_bbox;
+            lower.max[axis] = mid - 1;
+            self.search_node(search, depth + 1, child_id + 1, lower, shape_indices);
+        }
+        if search.max[axis] >= mid {
+            let mut upper = node_bbox;
+            upper.min[axis] = mid;
+            self.search_node(search, depth + 1, child_id, upper, shape_indices);
+        }
+    }
+}
+
+/// Converts a range of coordinates to the [0, 255] space of the extent,
+/// rounding outwards
+fn to_byte_range(range: [f64; 2], extent: [f64; 2]) -> [u8; 2] {
+    let size = extent[1] - extent[0];
+    if size == 0.0 {
+        return [0, 255];
+    }
+    let min = ((range[0] - extent[0]) / size * 255.0 - 0.005).floor();
+    let max = ((range[1] - extent[0]) / size * 255.0 + 0.005).ceil();
+    [min.clamp(0.0, 255.0) as u8, max.clamp(0.0, 255.0) as u8]
+}
+
+/// Fills the buffer, returns false if the source was already at its end
+fn read_exact_or_eof<T: Read>(source: &mut T, buf: &mut [u8]) -> Result<bool, Error> {
+    let mut num_read = 0;
+    while num_read < buf.len() {
+        match source.read(&mut buf[num_read..])? {
+            0 if num_read == 0 => return Ok(false),
+            0 => return Err(Error::IoError(std::io::ErrorKind::UnexpectedEof.into())),
+            n => num_read += n,
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+
+    /// Builds a .sbn with a tree of depth 2, over the [0, 255] extent
+    fn sbn_with_three_shapes() -> Vec<u8> {
+        let mut sbn = vec![0x00, 0x00, 0x27, 0x0A, 0xFF, 0xFF, 0xFE, 0x70];
+        sbn.resize(28, 0);
+        sbn.write_i32::<BigEndian>(3).unwrap();
+        for value in &[0.0, 0.0, 255.0, 255.0] {
+            sbn.write_f64::<BigEndian>(*value).unwrap();
+        }
+        sbn.resize(SBN_HEADER_SIZE, 0);
+
+        // node descriptors: root, upper x half, lower x half
+        sbn.write_i32::<BigEndian>(1).unwrap();
+        sbn.write_i32::<BigEndian>(12).unwrap();
+        for &(bin_start, count) in &[(2, 1), (3, 1), (4, 1)] {
+            sbn.write_i32::<BigEndian>(bin_start).unwrap();
+            sbn.write_i32::<BigEndian>(count).unwrap();
+        }
+
+        let shapes: [([u8; 4], i32); 3] = [
+            ([100, 100, 150, 150], 3),
+            ([200, 10, 220, 20], 2),
+            ([10, 10, 20, 20], 1),
+        ];
+        for (i, &(bbox, id)) in shapes.iter().enumerate() {
+            sbn.write_i32::<BigEndian>(i as i32 + 2).unwrap();
+            sbn.write_i32::<BigEndian>(4).unwrap();
+            sbn.extend_from_slice(&bbox);
+            sbn.write_i32::<BigEndian>(id).unwrap();
+        }
+        let file_length = sbn.len() as i32 / 2;
+        (&mut sbn[24..28])
+            .write_i32::<BigEndian>(file_length)
+            .unwrap();
+        sbn
+    }
+
+    #[test]
+    fn query() {
+        let index = SpatialIndex::read_from(sbn_with_three_shapes().as_slice()).unwrap();
+        assert_eq!(index.num_shapes(), 3);
+
+        let bbox = |min_x, min_y, max_x, max_y| GenericBBox {
+            min: Point::new(min_x, min_y),
+            max: Point::new(max_x, max_y),
+        };
+        assert_eq!(index.query(&bbox(0.0, 0.0, 50.0, 50.0)), vec![0]);
+        assert_eq!(index.query(&bbox(190.0, 0.0, 255.0, 30.0)), vec![1]);
+        assert_eq!(index.query(&bbox(0.0, 0.0, 255.0, 255.0)), vec![0, 1, 2]);
+        assert_eq!(index.query(&bbox(120.0, 120.0, 130.0, 130.0)), vec![2]);
+        assert_eq!(
+            index.query(&bbox(300.0, 300.0, 400.0, 400.0)),
+            Vec::<usize>::new()
+        );
+    }
+
+    #[test]
+    fn number_of_shapes_larger_than_the_file() {
+        let mut sbn = sbn_with_three_shapes();
+        (&mut sbn[28..32]).write_i32::<BigEndian>(i32::MAX).unwrap();
+        assert!(SpatialIndex::read_from(sbn.as_slice()).is_err());
+
+        // The file length is not trusted either, only the descriptors read are allocated
+        (&mut sbn[24..28]).write_i32::<BigEndian>(i32::MAX).unwrap();
+        (&mut sbn[28..32]).write_i32::<BigEndian>(1 << 28).unwrap();
+        sbn.truncate(SBN_HEADER_SIZE + 8 + 3 * 8);
+        assert!(SpatialIndex::read_from(sbn.as_slice()).is_err());
+    }
+
+    #[test]
+    fn invalid_file_code() {
+        let mut sbn = sbn_with_three_shapes();
+        sbn[3] = 0x0B;
+        assert!(SpatialIndex::read_from(sbn.as_slice()).is_err());
+    }
+}
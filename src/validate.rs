@@ -0,0 +1,705 @@
+//! Module to check that a shapefile follows the
+//! [ESRI specification](http://downloads.esri.com/support/whitepapers/mo_/shapefile.pdf)
+//!
+//! The [Validator](struct.Validator.html) reads the whole .shp (and optionally the .shx and .dbf)
+//! and returns a [ValidationReport](struct.ValidationReport.html) listing every
+//! problem found, instead of stopping at the first one like the reader does.
+//!
+//! The checks are:
+//!
+//! - the record numbers start at 1 and are consecutive,
+//! - the `file_length` of the header is the real length of the .shp,
+//! - the bbox of the header contains the bbox of every record (x and y only),
+//! - the shape type of every record is the one of the header, or Null,
+//! - the rings of polygons and multipatches are closed and have at least 4 points,
+//! - the parts of multipatches follow the rules of the specification (page 24):
+//!   inner rings follow an outer ring, rings follow a first ring,
+//!   triangle strips and fans have at least 3 points,
+//! - the entries of the .shx are the position and the length of the records of the .shp,
+//! - the .dbf has as many records as the .shp.
+//!
+//! The parts of multipatches must not intersect each other, this is **not** checked.
+//!
+//! # Example
+//!
+//! ```
+//! let report = shapefile::validate::validate_path("tests/data/polygon_hole.shp").unwrap();
+//! assert!(report.is_valid());
+//! for issue in report.issues() {
+//!     println!("{}", issue);
+//! }
+//! ```
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use dbf::TableHeader;
+use header;
+use index::{read_index_file, ShapeIndex};
+use record::RecordHeader;
+use {Error, ShapeType};
+
+/// A problem found in the shapefile
+#[derive(Debug, Clone, PartialEq)]
+pub enum IssueKind {
+    /// The `file_length` of the header is not the length of the .shp (in bytes)
+    FileLengthMismatch { declared: u64, actual: u64 },
+    /// The record number is not the one following the previous record
+    RecordNumberMismatch { expected: i32, actual: i32 },
+    /// The content length of the record is negative, or goes past the end of the file
+    ///
+    /// The records after it cannot be checked.
+    InvalidContentLength(i32),
+    /// The content of the record cannot be decoded
+    InvalidContent(String),
+    /// The shape type code of the record is unknown
+    InvalidShapeType(i32),
+    /// The shape type of the record is neither the one of the header nor Null
+    ShapeTypeMismatch {
+        header: ShapeType,
+        record: ShapeType,
+    },
+    /// The bbox of the record is not in the bbox of the header
+    BBoxOutsideHeader,
+    /// The first and last points of the ring are not the same
+    UnclosedRing { part: usize },
+    /// The ring has less than 4 points
+    RingTooShort { part: usize, num_points: usize },
+    /// The part type code of the multipatch part is unknown
+    InvalidPatchType { part: usize, code: i32 },
+    /// The multipatch inner ring does not follow an outer ring (or another inner ring)
+    InnerRingWithoutOuterRing { part: usize },
+    /// The multipatch ring does not follow a first ring (or another ring)
+    RingWithoutFirstRing { part: usize },
+    /// The multipatch triangle strip or fan has less than 3 points
+    TooFewTrianglePoints { part: usize, num_points: usize },
+    /// The .shx does not have one entry per record of the .shp
+    IndexLengthMismatch { shp: usize, shx: usize },
+    /// The entry of the .shx is not the position and content length (in 16-bit words)
+    /// of the record in the .shp
+    IndexEntryMismatch {
+        shx_offset: i32,
+        shx_content_length: i32,
+        shp_offset: i32,
+        shp_content_length: i32,
+    },
+    /// The .dbf does not have one record per record of the .shp
+    DbfLengthMismatch { shp: usize, dbf: usize },
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IssueKind::FileLengthMismatch { declared, actual } => write!(
+                f,
+                "the header declares {} bytes but the file has {} bytes",
+                declared, actual
+            ),
+            IssueKind::RecordNumberMismatch { expected, actual } => {
+                write!(f, "the record number is {} instead of {}", actual, expected)
+            }
+            IssueKind::InvalidContentLength(length) => {
+                write!(f, "the content length {} is invalid", length)
+            }
+            IssueKind::InvalidContent(msg) => write!(f, "the content is invalid: {}", msg),
+            IssueKind::InvalidShapeType(code) => write!(f, "the shape type {} is invalid", code),
+            IssueKind::ShapeTypeMismatch { header, record } => write!(
+                f,
+                "the shape type is '{}' but the header declares '{}'",
+                record, header
+            ),
+            IssueKind::BBoxOutsideHeader => write!(f, "the bbox is not in the bbox of the header"),
+            IssueKind::UnclosedRing { part } => write!(f, "the ring {} is not closed", part),
+            IssueKind::RingTooShort { part, num_points } => write!(
+                f,
+                "the ring {} has {} points, at least 4 are needed",
+                part, num_points
+            ),
+            IssueKind::InvalidPatchType { part, code } => {
+                write!(f, "the part {} has the invalid type {}", part, code)
+            }
+            IssueKind::InnerRingWithoutOuterRing { part } => {
+                write!(f, "the inner ring {} does not follow an outer ring", part)
+            }
+            IssueKind::RingWithoutFirstRing { part } => {
+                write!(f, "the ring {} does not follow a first ring", part)
+            }
+            IssueKind::TooFewTrianglePoints { part, num_points } => write!(
+                f,
+                "the triangles of part {} have {} points, at least 3 are needed",
+                part, num_points
+            ),
+            IssueKind::IndexLengthMismatch { shp, shx } => write!(
+                f,
+                "the .shx has {} entries but the .shp has {} records",
+                shx, shp
+            ),
+            IssueKind::IndexEntryMismatch {
+                shx_offset,
+                shx_content_length,
+                shp_offset,
+                shp_content_length,
+            } => write!(
+                f,
+                "the .shx entry is (offset: {}, length: {}) instead of (offset: {}, length: {})",
+                shx_offset, shx_content_length, shp_offset, shp_content_length
+            ),
+            IssueKind::DbfLengthMismatch { shp, dbf } => {
+                write!(f, "the .dbf has {} records but the .shp has {}", dbf, shp)
+            }
+        }
+    }
+}
+
+/// A problem found in the shapefile, and the record it concerns
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    /// Number of the record (starting from 1), `None` for the problems of the whole file
+    pub record_number: Option<usize>,
+    pub kind: IssueKind,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.record_number {
+            Some(record_number) => write!(f, "record {}: {}", record_number, self.kind),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+/// The result of the validation of a shapefile
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationReport {
+    num_records: usize,
+    issues: Vec<Issue>,
+}
+
+impl ValidationReport {
+    /// Returns true if no problem was found
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Returns the problems found, in the order of the records
+    pub fn issues(&self) -> &[Issue] {
+        &self.issues
+    }
+
+    /// Returns the number of records found in the .shp
+    pub fn num_records(&self) -> usize {
+        self.num_records
+    }
+}
+
+/// Reads the bbox of a record, stored as xmin, ymin, xmax, ymax
+fn read_bbox(content: &mut &[u8]) -> std::io::Result<[f64; 4]> {
+    let mut bbox = [0.0; 4];
+    for value in &mut bbox {
+        *value = content.read_f64::<LittleEndian>()?;
+    }
+    Ok(bbox)
+}
+
+/// The parts of a polygon or multipatch record
+struct Parts {
+    /// Index of the first point of each part
+    starts: Vec<usize>,
+    /// Part types, only for multipatches
+    types: Vec<i32>,
+    /// xy of the points
+    points: Vec<[f64; 2]>,
+}
+
+impl Parts {
+    fn read_from(content: &mut &[u8], has_types: bool) -> Result<Self, String> {
+        let too_short = |_| "the record is too short".to_string();
+        let num_parts = content.read_i32::<LittleEndian>().map_err(too_short)?;
+        let num_points = content.read_i32::<LittleEndian>().map_err(too_short)?;
+        if num_parts < 0 || num_points < 0 {
+            return Err("negative number of parts or points".to_string());
+        }
+        // Avoids huge allocations for corrupted counts
+        let size_needed =
+            num_parts as usize * if has_types { 8 } else { 4 } + num_points as usize * 16;
+        if content.len() < size_needed {
+            return Err("the record is too short".to_string());
+        }
+
+        let mut starts = Vec::with_capacity(num_parts as usize);
+        for _ in 0..num_parts {
+            starts.push(content.read_i32::<LittleEndian>().map_err(too_short)?);
+        }
+        let mut types = Vec::new();
+        if has_types {
+            for _ in 0..num_parts {
+                types.push(content.read_i32::<LittleEndian>().map_err(too_short)?);
+            }
+        }
+        let mut points = Vec::with_capacity(num_points as usize);
+        for _ in 0..num_points {
+            let x = content.read_f64::<LittleEndian>().map_err(too_short)?;
+            let y = content.read_f64::<LittleEndian>().map_err(too_short)?;
+            points.push([x, y]);
+        }
+
+        let is_valid_start = |(i, start): (usize, &i32)| {
+            *start >= 0 && *start <= num_points && (i == 0 || starts[i - 1] <= *start)
+        };
+        if !starts.iter().enumerate().all(is_valid_start) {
+            return Err("the index of the first point of a part is invalid".to_string());
+        }
+        Ok(Parts {
+            starts: starts.into_iter().map(|start| start as usize).collect(),
+            types,
+            points,
+        })
+    }
+
+    fn part_points(&self, part: usize) -> &[[f64; 2]] {
+        let end = self
+            .starts
+            .get(part + 1)
+            .cloned()
+            .unwrap_or(self.points.len());
+        &self.points[self.starts[part]..end]
+    }
+}
+
+fn check_ring(points: &[[f64; 2]], part: usize, issues: &mut Vec<IssueKind>) {
+    if points.len() < 4 {
+        issues.push(IssueKind::RingTooShort {
+            part,
+            num_points: points.len(),
+        });
+    }
+    if points.first() != points.last() {
+        issues.push(IssueKind::UnclosedRing { part });
+    }
+}
+
+fn check_multipatch_parts(parts: &Parts, issues: &mut Vec<IssueKind>) {
+    for (part, code) in parts.types.iter().enumerate() {
+        let points = parts.part_points(part);
+        let previous = if part > 0 {
+            Some(parts.types[part - 1])
+        } else {
+            None
+        };
+        match code {
+            // Triangle strip and fan
+            0 | 1 if points.len() < 3 => issues.push(IssueKind::TooFewTrianglePoints {
+                part,
+                num_points: points.len(),
+            }),
+            0 | 1 => {}
+            // Outer and first ring
+            2 | 4 => check_ring(points, part, issues),
+            // Inner ring
+            3 => {
+                if previous != Some(2) && previous != Some(3) {
+                    issues.push(IssueKind::InnerRingWithoutOuterRing { part });
+                }
+                check_ring(points, part, issues);
+            }
+            // Ring
+            5 => {
+                if previous != Some(4) && previous != Some(5) {
+                    issues.push(IssueKind::RingWithoutFirstRing { part });
+                }
+                check_ring(points, part, issues);
+            }
+            code => issues.push(IssueKind::InvalidPatchType { part, code: *code }),
+        }
+    }
+}
+
+/// Checks the content of a record (starting with its shape type)
+fn check_record_content(header: &header::Header, content: &[u8], issues: &mut Vec<IssueKind>) {
+    let mut content = content;
+    let too_short = |_| IssueKind::InvalidContent("the record is too short".to_string());
+
+    let result = content
+        .read_i32::<LittleEndian>()
+        .map_err(too_short)
+        .and_then(|code| ShapeType::from(code).ok_or(IssueKind::InvalidShapeType(code)));
+    let shape_type = match result {
+        Ok(ShapeType::NullShape) => return,
+        Ok(shape_type) => shape_type,
+        Err(issue) => return issues.push(issue),
+    };
+    if shape_type != header.shape_type {
+        issues.push(IssueKind::ShapeTypeMismatch {
+            header: header.shape_type,
+            record: shape_type,
+        });
+    }
+
+    let bbox = match shape_type {
+        ShapeType::Point | ShapeType::PointM | ShapeType::PointZ => {
+            let x = content.read_f64::<LittleEndian>();
+            let y = content.read_f64::<LittleEndian>();
+            match (x, y) {
+                (Ok(x), Ok(y)) => [x, y, x, y],
+                (Err(e), _) | (_, Err(e)) => return issues.push(too_short(e)),
+            }
+        }
+        _ => match read_bbox(&mut content) {
+            Ok(bbox) => bbox,
+            Err(e) => return issues.push(too_short(e)),
+        },
+    };
+    let hbox = &header.bbox;
+    if bbox[0] < hbox.min.x || bbox[1] < hbox.min.y || bbox[2] > hbox.max.x || bbox[3] > hbox.max.y
+    {
+        issues.push(IssueKind::BBoxOutsideHeader);
+    }
+
+    let has_types = match shape_type {
+        ShapeType::Polygon | ShapeType::PolygonM | ShapeType::PolygonZ => false,
+        ShapeType::Multipatch => true,
+        _ => return,
+    };
+    let parts = match Parts::read_from(&mut content, has_types) {
+        Ok(parts) => parts,
+        Err(msg) => return issues.push(IssueKind::InvalidContent(msg)),
+    };
+    if has_types {
+        check_multipatch_parts(&parts, issues);
+    } else {
+        for part in 0..parts.starts.len() {
+            check_ring(parts.part_points(part), part, issues);
+        }
+    }
+}
+
+/// Struct that checks a shapefile against the specification
+///
+/// Like the [Reader](../reader/struct.Reader.html), the .shx and the .dbf are optional,
+/// they are checked against the .shp when given.
+pub struct Validator<T: Read> {
+    source: T,
+    shapes_index: Option<Vec<ShapeIndex>>,
+    num_dbf_records: Option<usize>,
+}
+
+impl<T: Read> Validator<T> {
+    /// Creates a validator of the .shp read from the source
+    pub fn new(source: T) -> Self {
+        Self {
+            source,
+            shapes_index: None,
+            num_dbf_records: None,
+        }
+    }
+
+    /// Reads the .shx from the source, its entries will be compared to the records of the .shp
+    pub fn add_index_source<I: Read>(&mut self, source: I) -> Result<(), Error> {
+        self.shapes_index = Some(read_index_file(source)?);
+        Ok(())
+    }
+
+    /// Reads the header of the .dbf from the source,
+    /// its number of records will be compared to the one of the .shp
+    pub fn add_dbf_source<D: Read>(&mut self, source: D) -> Result<(), Error> {
+        self.num_dbf_records = Some(TableHeader::read_from(source)?.num_records());
+        Ok(())
+    }
+
+    /// Reads the whole .shp and returns the problems found
+    ///
+    /// # Errors
+    ///
+    /// Only the errors that prevent from reading the file are returned:
+    /// I/O errors and invalid header (see [Reader::new](../reader/struct.Reader.html#method.new)).
+    pub fn validate(mut self) -> Result<ValidationReport, Error> {
+        let header = header::Header::read_from(&mut self.source)?;
+        let mut issues = Vec::<Issue>::new();
+        // Position and content length of each record, in 16-bit words
+        let mut records = Vec::<ShapeIndex>::new();
+        let mut pos = header::HEADER_SIZE as u64;
+        let mut content = Vec::<u8>::new();
+
+        loop {
+            let mut hdr_bytes = [0u8; RecordHeader::SIZE];
+            let num_read = read_up_to(&mut self.source, &mut hdr_bytes)?;
+            pos += num_read as u64;
+            if num_read == 0 {
+                break;
+            }
+            let record_number = records.len() + 1;
+            if num_read < RecordHeader::SIZE {
+                issues.push(Issue {
+                    record_number: Some(record_number),
+                    kind: IssueKind::InvalidContent("the record header is truncated".to_string()),
+                });
+                break;
+            }
+            let hdr = RecordHeader::read_from(&mut &hdr_bytes[..])?;
+            if hdr.record_number != record_number as i32 {
+                issues.push(Issue {
+                    record_number: Some(record_number),
+                    kind: IssueKind::RecordNumberMismatch {
+                        expected: record_number as i32,
+                        actual: hdr.record_number,
+                    },
+                });
+            }
+
+            content.clear();
+            let content_length = i64::from(hdr.record_size) * 2;
+            if content_length >= 0 {
+                (&mut self.source)
+                    .take(content_length as u64)
+                    .read_to_end(&mut content)?;
+                pos += content.len() as u64;
+            }
+            if content_length < 0 || content.len() as i64 != content_length {
+                issues.push(Issue {
+                    record_number: Some(record_number),
+                    kind: IssueKind::InvalidContentLength(hdr.record_size),
+                });
+                // Counts the remaining bytes to know the length of the file
+                pos += std::io::copy(&mut self.source, &mut std::io::sink())?;
+                break;
+            }
+
+            records.push(ShapeIndex {
+                offset: ((pos - content.len() as u64 - RecordHeader::SIZE as u64) / 2) as i32,
+                record_size: hdr.record_size,
+            });
+            let mut record_issues = Vec::<IssueKind>::new();
+            check_record_content(&header, &content, &mut record_issues);
+            issues.extend(record_issues.into_iter().map(|kind| Issue {
+                record_number: Some(record_number),
+                kind,
+            }));
+        }
+
+        let declared = header.file_length.max(0) as u64 * 2;
+        if declared != pos {
+            issues.push(Issue {
+                record_number: None,
+                kind: IssueKind::FileLengthMismatch {
+                    declared,
+                    actual: pos,
+                },
+            });
+        }
+
+        if let Some(ref shapes_index) = self.shapes_index {
+            if shapes_index.len() != records.len() {
+                issues.push(Issue {
+                    record_number: None,
+                    kind: IssueKind::IndexLengthMismatch {
+                        shp: records.len(),
+                        shx: shapes_index.len(),
+                    },
+                });
+            }
+            for (i, (entry, record)) in shapes_index.iter().zip(&records).enumerate() {
+                if entry.offset != record.offset || entry.record_size != record.record_size {
+                    issues.push(Issue {
+                        record_number: Some(i + 1),
+                        kind: IssueKind::IndexEntryMismatch {
+                            shx_offset: entry.offset,
+                            shx_content_length: entry.record_size,
+                            shp_offset: record.offset,
+                            shp_content_length: record.record_size,
+                        },
+                    });
+                }
+            }
+        }
+
+        if let Some(num_dbf_records) = self.num_dbf_records {
+            if num_dbf_records != records.len() {
+                issues.push(Issue {
+                    record_number: None,
+                    kind: IssueKind::DbfLengthMismatch {
+                        shp: records.len(),
+                        dbf: num_dbf_records,
+                    },
+                });
+            }
+        }
+
+        Ok(ValidationReport {
+            num_records: records.len(),
+            issues,
+        })
+    }
+}
+
+/// Reads until the buffer is full or the end of the source is reached
+fn read_up_to<T: Read>(source: &mut T, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut num_read = 0;
+    while num_read < buffer.len() {
+        match source.read(&mut buffer[num_read..]) {
+            Ok(0) => break,
+            Ok(n) => num_read += n,
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(num_read)
+}
+
+/// Validates the shapefile at the given path,
+/// with the .shx and .dbf next to it if they exist
+pub fn validate_path<P: AsRef<Path>>(path: P) -> Result<ValidationReport, Error> {
+    let shp_path = path.as_ref().to_path_buf();
+    let mut validator = Validator::new(BufReader::new(File::open(&shp_path)?));
+
+    let shx_path = shp_path.with_extension("shx");
+    if shx_path.exists() {
+        validator.add_index_source(BufReader::new(File::open(shx_path)?))?;
+    }
+
+    let dbf_path = shp_path.with_extension("dbf");
+    if dbf_path.exists() {
+        validator.add_dbf_source(BufReader::new(File::open(dbf_path)?))?;
+    }
+    validator.validate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use writer::Writer;
+    use {Multipatch, Patch, Point, PointZ, Polygon, PolygonRing};
+
+    fn write_shapes<S: ::record::EsriShape>(shapes: &[S]) -> (Vec<u8>, Vec<u8>) {
+        let mut shp = Vec::<u8>::new();
+        let mut shx = Vec::<u8>::new();
+        {
+            let mut writer = Writer::new(&mut shp);
+            writer.add_index_dest(&mut shx);
+            writer.write_shapes(shapes).unwrap();
+        }
+        (shp, shx)
+    }
+
+    fn validate(shp: &[u8], shx: Option<&[u8]>) -> Vec<Issue> {
+        let mut validator = Validator::new(Cursor::new(shp));
+        if let Some(shx) = shx {
+            validator.add_index_source(shx).unwrap();
+        }
+        validator.validate().unwrap().issues
+    }
+
+    #[test]
+    fn valid_polygons() {
+        let polygon = Polygon::new(PolygonRing::Outer(vec![
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 1.0),
+            Point::new(1.0, 1.0),
+            Point::new(0.0, 0.0),
+        ]));
+        let (shp, shx) = write_shapes(&[polygon.clone(), polygon]);
+        assert_eq!(validate(&shp, Some(&shx)), vec![]);
+
+        // Second record number and last point of the ring
+        let mut corrupted = shp.clone();
+        let second_record = shp.len() / 2 + 50;
+        corrupted[second_record..second_record + 4].copy_from_slice(&5i32.to_be_bytes());
+        let last_y = shp.len() - 8;
+        corrupted[last_y..].copy_from_slice(&0.5f64.to_le_bytes());
+        let issues = validate(&corrupted, Some(&shx));
+        assert_eq!(
+            issues,
+            vec![
+                Issue {
+                    record_number: Some(2),
+                    kind: IssueKind::RecordNumberMismatch {
+                        expected: 2,
+                        actual: 5
+                    }
+                },
+                Issue {
+                    record_number: Some(2),
+                    kind: IssueKind::UnclosedRing { part: 0 }
+                },
+            ]
+        );
+
+        // Missing bytes at the end, and the .shx does not match anymore
+        let issues = validate(&shp[..shp.len() - 8], Some(&shx));
+        let kinds = issues.into_iter().map(|i| i.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                IssueKind::InvalidContentLength(56),
+                IssueKind::FileLengthMismatch {
+                    declared: shp.len() as u64,
+                    actual: shp.len() as u64 - 8
+                },
+                IssueKind::IndexLengthMismatch { shp: 1, shx: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_index_file_length() {
+        let (_, shx) = write_shapes(&[Point::new(0.0, 0.0)]);
+        let mut validator = Validator::new(Cursor::new(Vec::<u8>::new()));
+
+        let mut corrupted = shx.clone();
+        corrupted[24..28].copy_from_slice(&10i32.to_be_bytes());
+        match validator.add_index_source(corrupted.as_slice()) {
+            Err(e) => match e.without_context() {
+                Error::InvalidFileLength(10) => {}
+                e => panic!("unexpected error {}", e),
+            },
+            Ok(_) => panic!("the .shx should be rejected"),
+        }
+
+        // The entries that the header announces are not in the file
+        let mut corrupted = shx;
+        corrupted[24..28].copy_from_slice(&i32::MAX.to_be_bytes());
+        match validator.add_index_source(corrupted.as_slice()) {
+            Err(e) => match e.without_context() {
+                Error::IoError(_) => {}
+                e => panic!("unexpected error {}", e),
+            },
+            Ok(_) => panic!("the .shx should be rejected"),
+        }
+    }
+
+    #[test]
+    fn multipatch_parts_rules() {
+        let square = vec![
+            PointZ::new(0.0, 0.0, 0.0, 0.0),
+            PointZ::new(0.0, 1.0, 0.0, 0.0),
+            PointZ::new(1.0, 1.0, 0.0, 0.0),
+            PointZ::new(0.0, 0.0, 0.0, 0.0),
+        ];
+        let multipatch = Multipatch::with_parts(vec![
+            Patch::InnerRing(square.clone()),
+            Patch::Ring(square.clone()),
+            Patch::TriangleFan(square[..2].to_vec()),
+            Patch::OuterRing(square.clone()),
+            Patch::InnerRing(square),
+        ]);
+        let (shp, _) = write_shapes(&[multipatch]);
+        let kinds = validate(&shp, None)
+            .into_iter()
+            .map(|i| i.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                IssueKind::InnerRingWithoutOuterRing { part: 0 },
+                IssueKind::RingWithoutFirstRing { part: 1 },
+                IssueKind::TooFewTrianglePoints {
+                    part: 2,
+                    num_points: 2
+                },
+            ]
+        );
+    }
+}
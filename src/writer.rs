@@ -3,17 +3,25 @@
 //! It is recommended to create a `Writer` using its [from_path](struct.Writer.html#method.from_path) method
 //! to ensure that both the .shp and .shx files are created.
 //! Then use its [writes_shapes](struct.Writer.html#method.write_shapes) method to write the files.
+//!
+//! When the shapes are not all available at once (e.g. they are produced by a pipeline
+//! and would not fit in memory), use a [ShapeWriter](struct.ShapeWriter.html) which
+//! writes the shapes one at a time.
+//! The [FeatureWriter](struct.FeatureWriter.html) does the same for shapes and their
+//! dbf records.
 
-use std::io::{BufWriter, Write};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
 
+use dbf::{cpg_of_encoding, FieldInfo, TableWriter};
+use encoding_rs::Encoding;
 use header;
+use index::{write_index_file, ShapeIndex};
+use projection::Projection;
+use qix::QuadTree;
 use record::{BBoxZ, EsriShape, RecordHeader};
 use std::fs::File;
-use std::path::Path;
-use Error;
-
-use byteorder::{BigEndian, WriteBytesExt};
-use reader::ShapeIndex;
+use std::path::{Path, PathBuf};
+use {Error, FileRole, ShapeType};
 
 pub(crate) fn f64_min(a: f64, b: f64) -> f64 {
     if a < b {
@@ -31,22 +39,20 @@ pub(crate) fn f64_max(a: f64, b: f64) -> f64 {
     }
 }
 
-fn write_index_file<T: Write>(
-    mut dest: &mut T,
-    shapefile_header: &header::Header,
-    shapes_index: Vec<ShapeIndex>,
-) -> Result<(), std::io::Error> {
-    let mut header = *shapefile_header;
-    let content_len = shapes_index.len() * 2 * std::mem::size_of::<i32>();
-    header.file_length = header::HEADER_SIZE + content_len as i32;
-    header.file_length /= 2;
+/// Returns the size in bytes of the record of the shape, including its header
+pub(crate) fn size_of_record<S: EsriShape>(shape: &S) -> u64 {
+    (RecordHeader::SIZE + std::mem::size_of::<i32>() + shape.size_in_bytes()) as u64
+}
 
-    header.write_to(&mut dest)?;
-    for shape_index in shapes_index {
-        dest.write_i32::<BigEndian>(shape_index.offset)?;
-        dest.write_i32::<BigEndian>(shape_index.record_size)?;
-    }
-    Ok(())
+/// Writes the record of the shape: its header, shape type and content
+pub(crate) fn write_record<T: Write, S: EsriShape>(
+    dest: &mut T,
+    rc_hdr: &RecordHeader,
+    shape: &S,
+) -> Result<(), Error> {
+    rc_hdr.write_to(dest)?;
+    shape.record_shapetype().write_to(dest)?;
+    shape.write_to(dest)
 }
 
 /// struct that writes the shapes
@@ -54,6 +60,7 @@ pub struct Writer<T: Write> {
     pub dest: T,
     index_dest: Option<T>,
     dbase_dest: Option<T>,
+    qix_dest: Option<T>,
 }
 
 impl<T: Write> Writer<T> {
@@ -65,6 +72,7 @@ impl<T: Write> Writer<T> {
             dest,
             index_dest: None,
             dbase_dest: None,
+            qix_dest: None,
         }
     }
 
@@ -89,19 +97,44 @@ impl<T: Write> Writer<T> {
     ///
     /// writer.write_shapes(&vec![polyline]).unwrap();
     /// ```
+    ///
+    /// Shapes given as `Option<S>` are written as Null shapes when `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::MismatchShapeType` if the shapes that are not
+    /// Null shapes do not all have the same type.
+    ///
+    /// Returns `Error::FileTooLarge` if the shapes do not fit in a .shp
+    /// (see [MAX_FILE_SIZE](../header/constant.MAX_FILE_SIZE.html)),
+    /// in both cases nothing is written.
     pub fn write_shapes<S: EsriShape>(&mut self, shapes: &[S]) -> Result<(), Error> {
-        let mut file_length = header::HEADER_SIZE as usize;
-        for shape in shapes {
-            file_length += 2 * std::mem::size_of::<i32>(); // record_header
-            file_length += std::mem::size_of::<i32>(); // shape_type
-            file_length += shape.size_in_bytes();
+        let mut file_size = header::HEADER_SIZE as u64;
+        for (index, shape) in shapes.iter().enumerate() {
+            file_size += size_of_record(shape);
+            if file_size > header::MAX_FILE_SIZE {
+                let error = Error::FileTooLarge;
+                return Err(error.with_context(FileRole::Shp, Some(index), None));
+            }
+        }
+        // file size is in 16bit words
+        let file_length = (file_size / 2) as i32;
+        // Null shapes are allowed in any file, the type of the file is the one of the other shapes
+        let mut non_null_shapes = shapes
+            .iter()
+            .map(EsriShape::record_shapetype)
+            .enumerate()
+            .filter(|(_, shapetype)| *shapetype != ShapeType::NullShape);
+        let shapetype = non_null_shapes
+            .next()
+            .map_or_else(S::shapetype, |(_, shapetype)| shapetype);
+        if let Some((index, actual)) = non_null_shapes.find(|(_, actual)| *actual != shapetype) {
+            let error = Error::MismatchShapeType {
+                requested: shapetype,
+                actual,
+            };
+            return Err(error.with_context(FileRole::Shp, Some(index), None));
         }
-        file_length /= 2; // file size is in 16bit words
-
-        assert!(file_length <= i32::max_value() as usize);
-
-        let file_length = file_length as i32;
-        let shapetype = S::shapetype();
         let header = header::Header {
             bbox: BBoxZ::from_shapes(shapes),
             file_length,
@@ -110,10 +143,12 @@ impl<T: Write> Writer<T> {
         };
 
         let mut pos = header::HEADER_SIZE / 2;
-        header.write_to(&mut self.dest)?;
+        header
+            .write_to(&mut self.dest)
+            .map_err(|e| Error::IoError(e).with_context(FileRole::Shp, None, Some(0)))?;
         let mut shapes_index = Vec::<ShapeIndex>::with_capacity(shapes.len());
         for (i, shape) in (1..).zip(shapes) {
-            //TODO Check record size < i32_max ?
+            // Fits in an i32 as the file length does
             let record_size = (shape.size_in_bytes() + std::mem::size_of::<i32>()) / 2;
             let rc_hdr = RecordHeader {
                 record_number: i,
@@ -125,14 +160,20 @@ impl<T: Write> Writer<T> {
                 record_size: record_size as i32,
             });
 
-            rc_hdr.write_to(&mut self.dest)?;
-            shapetype.write_to(&mut self.dest)?;
-            shape.write_to(&mut self.dest)?;
+            write_record(&mut self.dest, &rc_hdr, shape).map_err(|e| {
+                let index = Some(i as usize - 1);
+                e.with_context(FileRole::Shp, index, Some(pos as u64 * 2))
+            })?;
             pos += record_size as i32 + RecordHeader::SIZE as i32 / 2;
         }
 
         if let Some(ref mut shx_dest) = &mut self.index_dest {
-            write_index_file(shx_dest, &header, shapes_index)?;
+            write_index_file(shx_dest, &header, shapes_index)
+                .map_err(|e| e.with_context(FileRole::Shx, None, None))?;
+        }
+
+        if let Some(ref mut qix_dest) = &mut self.qix_dest {
+            QuadTree::from_shapes(shapes).write_to(qix_dest)?;
         }
 
         Ok(())
@@ -144,12 +185,17 @@ impl<T: Write> Writer<T> {
         records: Vec<dbase::Record>,
     ) -> Result<(), Error> {
         if shapes.len() != records.len() {
-            panic!("The shapes and records vectors must have the same len");
+            return Err(Error::MismatchRecordCount {
+                shapes: shapes.len(),
+                records: records.len(),
+            });
         }
         self.write_shapes(&shapes)?;
         if let Some(dbase_dest) = self.dbase_dest {
             let dbase_writer = dbase::Writer::new(dbase_dest);
-            dbase_writer.write(&records)?;
+            dbase_writer
+                .write(&records)
+                .map_err(|e| Error::DbaseError(e).with_context(FileRole::Dbf, None, None))?;
         }
         Ok(())
     }
@@ -163,6 +209,24 @@ impl<T: Write> Writer<T> {
     pub fn add_dbase_dest(&mut self, dest: T) {
         self.dbase_dest = Some(dest);
     }
+
+    /// Adds dest as the destination where the .qix quadtree index of the shapes will be written
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use std::io::BufWriter;
+    /// use shapefile::Point;
+    /// let mut writer = shapefile::Writer::from_path("points_with_qix.shp").unwrap();
+    /// writer.add_qix_dest(BufWriter::new(File::create("points_with_qix.qix").unwrap()));
+    /// writer.write_shapes(&[Point::new(0.0, 0.0), Point::new(1.0, 0.0)]).unwrap();
+    /// # drop(writer);
+    /// # std::fs::remove_file("points_with_qix.qix").unwrap();
+    /// ```
+    pub fn add_qix_dest(&mut self, dest: T) {
+        self.qix_dest = Some(dest);
+    }
 }
 
 impl Writer<BufWriter<File>> {
@@ -190,4 +254,492 @@ impl Writer<BufWriter<File>> {
 
         Ok(writer)
     }
+
+    /// Creates a new writer from a path, and writes the projection
+    /// in a .prj next to the .shp and .shx
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use shapefile::projection::Projection;
+    /// use shapefile::Point;
+    ///
+    /// let projection = Projection::from_wkt(r#"GEOGCS["GCS_WGS_1984",DATUM["D_WGS_1984",SPHEROID["WGS_1984",6378137.0,298.257223563]],PRIMEM["Greenwich",0.0],UNIT["Degree",0.0174532925199433]]"#).unwrap();
+    /// let mut writer = shapefile::Writer::from_path_with_projection("projected_points.shp", &projection).unwrap();
+    /// writer.write_shapes(&[Point::new(2.35, 48.85)]).unwrap();
+    /// drop(writer);
+    ///
+    /// assert_eq!(Projection::from_path("projected_points.prj").unwrap(), projection);
+    /// # std::fs::remove_file("projected_points.prj").unwrap();
+    /// ```
+    pub fn from_path_with_projection<P: AsRef<Path>>(
+        path: P,
+        projection: &Projection,
+    ) -> Result<Self, Error> {
+        let writer = Self::from_path(path.as_ref())?;
+        projection.write_to_path(path.as_ref().with_extension("prj"))?;
+        Ok(writer)
+    }
+}
+
+/// struct that writes the shapes one at a time
+///
+/// Contrary to the [Writer](struct.Writer.html), the shapes do not have to be all
+/// known upfront: each shape is written as soon as it is given, while the bounding box
+/// and index entries are kept up to date.
+///
+/// The headers of the .shp and .shx are only correct once the writer is finalized,
+/// that is when [finalize](#method.finalize) is called or when the `ShapeWriter` is dropped.
+/// As errors cannot be reported when dropping, prefer calling `finalize`.
+///
+/// # Example
+///
+/// ```
+/// use shapefile::{Point, ShapeWriter};
+/// let mut writer = ShapeWriter::from_path("streamed_points.shp").unwrap();
+/// for i in 0..10 {
+///     writer.write_shape(&Point::new(i as f64, 0.0)).unwrap();
+/// }
+/// writer.finalize().unwrap();
+/// ```
+pub struct ShapeWriter<T: Write + Seek> {
+    dest: T,
+    index_dest: Option<T>,
+    header: header::Header,
+    shapes_index: Vec<ShapeIndex>,
+    /// Position of the header in the dest, known once the first shape is written
+    header_pos: Option<u64>,
+    /// The type and bbox of the header are the ones of the non-null shapes
+    has_non_null_shapes: bool,
+    finalized: bool,
+}
+
+impl<T: Write + Seek> ShapeWriter<T> {
+    /// Creates a writer that writes shapes one at a time.
+    ///
+    /// The `dest` argument is only for the .shp
+    pub fn new(dest: T) -> Self {
+        Self {
+            dest,
+            index_dest: None,
+            header: header::Header::default(),
+            shapes_index: Vec::new(),
+            header_pos: None,
+            has_non_null_shapes: false,
+            finalized: false,
+        }
+    }
+
+    /// Adds dest as the destination where the index file will be written
+    pub fn add_index_dest(&mut self, dest: T) {
+        self.index_dest = Some(dest);
+    }
+
+    /// Writes one shape
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::MismatchShapeType` if the shape type is not the same
+    /// as the one of the shapes previously written, Null shapes can be written
+    /// with shapes of any type.
+    ///
+    /// Returns `Error::FileTooLarge` if the .shp would be larger than
+    /// [MAX_FILE_SIZE](../header/constant.MAX_FILE_SIZE.html) with the shape.
+    pub fn write_shape<S: EsriShape>(&mut self, shape: &S) -> Result<(), Error> {
+        let index = self.shapes_index.len();
+        let offset = self.shp_size();
+        if offset + size_of_record(shape) > header::MAX_FILE_SIZE {
+            let error = Error::FileTooLarge;
+            return Err(error.with_context(FileRole::Shp, Some(index), Some(offset)));
+        }
+        let shapetype = shape.record_shapetype();
+        if shapetype != ShapeType::NullShape && self.has_non_null_shapes {
+            if shapetype != self.header.shape_type {
+                let error = Error::MismatchShapeType {
+                    requested: self.header.shape_type,
+                    actual: shapetype,
+                };
+                return Err(error.with_context(FileRole::Shp, Some(index), Some(offset)));
+            }
+            self.header.bbox.grow_from_shape(shape);
+        } else if shapetype != ShapeType::NullShape {
+            self.has_non_null_shapes = true;
+            self.header.shape_type = shapetype;
+            self.header.bbox = BBoxZ::from_shape(shape);
+        } else if self.header_pos.is_none() {
+            self.header.shape_type = S::shapetype();
+        }
+
+        if self.header_pos.is_none() {
+            self.header_pos = Some(self.dest.stream_position()?);
+            // Placeholder, the real header is written when finalizing
+            self.header
+                .write_to(&mut self.dest)
+                .map_err(|e| Error::IoError(e).with_context(FileRole::Shp, None, Some(0)))?;
+        }
+
+        let record_size = (shape.size_in_bytes() + std::mem::size_of::<i32>()) / 2;
+        let rc_hdr = RecordHeader {
+            record_number: index as i32 + 1,
+            record_size: record_size as i32,
+        };
+        write_record(&mut self.dest, &rc_hdr, shape)
+            .map_err(|e| e.with_context(FileRole::Shp, Some(index), Some(offset)))?;
+
+        self.shapes_index.push(ShapeIndex {
+            offset: self.header.file_length,
+            record_size: record_size as i32,
+        });
+        self.header.file_length += record_size as i32 + RecordHeader::SIZE as i32 / 2;
+        Ok(())
+    }
+
+    /// Returns the size in bytes of the .shp written so far, including its header
+    pub(crate) fn shp_size(&self) -> u64 {
+        self.header.file_length as u64 * 2
+    }
+
+    /// Writes the final headers of the .shp and the content of the .shx
+    ///
+    /// Writing shapes after the writer is finalized is not possible,
+    /// this is why this method consumes the writer.
+    pub fn finalize(mut self) -> Result<(), Error> {
+        self.finalize_files()
+    }
+
+    fn finalize_files(&mut self) -> Result<(), Error> {
+        if self.finalized {
+            return Ok(());
+        }
+        self.finalized = true;
+
+        self.finalize_shp()
+            .map_err(|e| Error::IoError(e).with_context(FileRole::Shp, None, Some(0)))?;
+
+        if let Some(ref mut shx_dest) = self.index_dest {
+            let shapes_index = std::mem::take(&mut self.shapes_index);
+            write_index_file(shx_dest, &self.header, shapes_index)
+                .and_then(|_| shx_dest.flush().map_err(Error::IoError))
+                .map_err(|e| e.with_context(FileRole::Shx, None, None))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the final header of the .shp
+    fn finalize_shp(&mut self) -> Result<(), std::io::Error> {
+        if let Some(header_pos) = self.header_pos {
+            let end_pos = self.dest.stream_position()?;
+            self.dest.seek(SeekFrom::Start(header_pos))?;
+            self.header.write_to(&mut self.dest)?;
+            self.dest.seek(SeekFrom::Start(end_pos))?;
+        } else {
+            // No shapes were written, we still want a valid (empty) shapefile
+            self.header.write_to(&mut self.dest)?;
+        }
+        self.dest.flush()
+    }
+}
+
+impl<T: Write + Seek> Drop for ShapeWriter<T> {
+    fn drop(&mut self) {
+        let _ = self.finalize_files();
+    }
+}
+
+impl ShapeWriter<BufWriter<File>> {
+    /// Creates a new streaming writer from a path.
+    /// Creates both a .shp and .shx files
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let writer = shapefile::ShapeWriter::from_path("streamed.shp");
+    /// assert!(writer.is_ok());
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let shp_path = path.as_ref().to_path_buf();
+        let shx_path = shp_path.with_extension("shx");
+
+        let shp_file = BufWriter::new(File::create(shp_path)?);
+        let shx_file = BufWriter::new(File::create(shx_path)?);
+
+        let mut writer = Self::new(shp_file);
+        writer.add_index_dest(shx_file);
+        Ok(writer)
+    }
+}
+
+/// struct that writes shapes and their dbf records one at a time
+///
+/// The fields of the dbf are declared when creating the writer,
+/// each record written is then checked against them.
+///
+/// # Example
+///
+/// ```
+/// use shapefile::dbase::{FieldValue, Record};
+/// use shapefile::dbf::FieldInfo;
+/// use shapefile::{FeatureWriter, Point};
+///
+/// let fields = vec![FieldInfo::character("NAME", 16).unwrap()];
+/// let mut writer = FeatureWriter::from_path("cities.shp", fields).unwrap();
+///
+/// let mut record = Record::new();
+/// record.insert("NAME".to_string(), FieldValue::Character(Some("Lyon".to_string())));
+/// writer.write_feature(&Point::new(4.83, 45.76), record).unwrap();
+///
+/// // The record does not match the fields
+/// let record = Record::new();
+/// assert!(writer.write_feature(&Point::new(2.35, 48.85), record).is_err());
+///
+/// writer.finalize().unwrap();
+/// ```
+pub struct FeatureWriter<T: Write + Seek> {
+    shape_writer: ShapeWriter<T>,
+    table_writer: TableWriter<T>,
+}
+
+impl<T: Write + Seek> FeatureWriter<T> {
+    /// Creates a writer from the writers of the shapes and of the records
+    pub fn new(shape_writer: ShapeWriter<T>, table_writer: TableWriter<T>) -> Self {
+        Self {
+            shape_writer,
+            table_writer,
+        }
+    }
+
+    /// Writes the shape and its record
+    ///
+    /// # Errors
+    ///
+    /// The record is checked against the declared fields before anything is written,
+    /// so that the shapes and records stay aligned if it does not match.
+    pub fn write_feature<S: EsriShape>(
+        &mut self,
+        shape: &S,
+        record: dbase::Record,
+    ) -> Result<(), Error> {
+        self.table_writer.check_record(&record)?;
+        self.shape_writer.write_shape(shape)?;
+        self.table_writer.write_record(&record)
+    }
+
+    /// Writes the final headers of the .shp and .dbf and the content of the .shx
+    pub fn finalize(self) -> Result<(), Error> {
+        self.shape_writer.finalize()?;
+        self.table_writer.finalize()
+    }
+}
+
+impl FeatureWriter<BufWriter<File>> {
+    /// Creates a new writer from a path.
+    /// Creates the .shp, .shx and .dbf files
+    pub fn from_path<P: AsRef<Path>>(path: P, fields: Vec<FieldInfo>) -> Result<Self, Error> {
+        let dbf_path = path.as_ref().with_extension("dbf");
+        let shape_writer = ShapeWriter::from_path(path)?;
+        let dbf_file = BufWriter::new(File::create(dbf_path)?);
+        let table_writer = TableWriter::new(dbf_file, fields)?;
+        Ok(Self::new(shape_writer, table_writer))
+    }
+
+    /// Creates a new writer from a path, the text of the records is encoded with `encoding`
+    ///
+    /// Creates the .shp, .shx and .dbf files, and the .cpg that declares the encoding.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use shapefile::dbase::{FieldValue, Record};
+    /// use shapefile::dbf::FieldInfo;
+    /// use shapefile::encoding_rs::WINDOWS_1252;
+    /// use shapefile::{FeatureWriter, Point};
+    ///
+    /// let fields = vec![FieldInfo::character("NAME", 16).unwrap()];
+    /// let mut writer = FeatureWriter::from_path_with_encoding("french_cities.shp", fields, WINDOWS_1252).unwrap();
+    /// let mut record = Record::new();
+    /// record.insert("NAME".to_string(), FieldValue::Character(Some("Besançon".to_string())));
+    /// writer.write_feature(&Point::new(6.02, 47.24), record).unwrap();
+    /// writer.finalize().unwrap();
+    ///
+    /// let reader = shapefile::Reader::from_path("french_cities.shp").unwrap();
+    /// assert_eq!(reader.dbf_encoding(), Some(WINDOWS_1252));
+    /// let records = reader.read_records().unwrap();
+    /// assert_eq!(records[0]["NAME"], FieldValue::Character(Some("Besançon".to_string())));
+    /// # std::fs::remove_file("french_cities.cpg").unwrap();
+    /// ```
+    pub fn from_path_with_encoding<P: AsRef<Path>>(
+        path: P,
+        fields: Vec<FieldInfo>,
+        encoding: &'static Encoding,
+    ) -> Result<Self, Error> {
+        let cpg_path = path.as_ref().with_extension("cpg");
+        let mut writer = Self::from_path(path, fields)?;
+        writer.table_writer.set_encoding(encoding);
+        std::fs::write(cpg_path, cpg_of_encoding(encoding))?;
+        Ok(writer)
+    }
+}
+
+/// struct that writes shapes and their records one at a time, like the
+/// [FeatureWriter](struct.FeatureWriter.html), in as many shapefiles as needed
+/// for each .shp to stay below a maximum size
+///
+/// The first part is written at the given path, the next ones are written next to it
+/// with a number added to the name: `roads.shp`, then `roads_001.shp`, `roads_002.shp`...
+/// Each part has its .shx and .dbf, as well as its .prj and .cpg when a projection
+/// or an encoding is given.
+///
+/// By default the max size of a .shp is [MAX_FILE_SIZE](../header/constant.MAX_FILE_SIZE.html),
+/// the limit of the format, it can be lowered with [set_max_shp_size](#method.set_max_shp_size)
+/// as some programs only read .shp smaller than 2 GB.
+///
+/// # Example
+///
+/// ```
+/// use shapefile::dbase::{FieldValue, Record};
+/// use shapefile::dbf::FieldInfo;
+/// use shapefile::{Point, SplittingWriter};
+///
+/// let fields = vec![FieldInfo::numeric("ID", 4, 0).unwrap()];
+/// let mut writer = SplittingWriter::from_path("split_points.shp", fields).unwrap();
+/// // Room for the header and 2 points
+/// writer.set_max_shp_size(156);
+/// for i in 0..5 {
+///     let mut record = Record::new();
+///     record.insert("ID".to_string(), FieldValue::Numeric(Some(i as f64)));
+///     writer.write_feature(&Point::new(i as f64, 0.0), record).unwrap();
+/// }
+/// let parts = writer.finalize().unwrap();
+/// assert_eq!(parts.len(), 3);
+/// assert_eq!(parts[2].to_str(), Some("split_points_002.shp"));
+/// # for part in parts {
+/// #     for extension in &["shp", "shx", "dbf"] {
+/// #         std::fs::remove_file(part.with_extension(extension)).unwrap();
+/// #     }
+/// # }
+/// ```
+pub struct SplittingWriter {
+    path: PathBuf,
+    fields: Vec<FieldInfo>,
+    encoding: Option<&'static Encoding>,
+    projection: Option<Projection>,
+    max_shp_size: u64,
+    /// Paths of the .shp of the parts, the last one being the one written by `writer`
+    parts: Vec<PathBuf>,
+    writer: FeatureWriter<BufWriter<File>>,
+}
+
+impl SplittingWriter {
+    /// Creates a new writer, the first part is created at the path
+    pub fn from_path<P: AsRef<Path>>(path: P, fields: Vec<FieldInfo>) -> Result<Self, Error> {
+        Self::create(path.as_ref(), fields, None)
+    }
+
+    /// Creates a new writer, the text of the records of all the parts is encoded with `encoding`
+    ///
+    /// (see [FeatureWriter::from_path_with_encoding](struct.FeatureWriter.html#method.from_path_with_encoding))
+    pub fn from_path_with_encoding<P: AsRef<Path>>(
+        path: P,
+        fields: Vec<FieldInfo>,
+        encoding: &'static Encoding,
+    ) -> Result<Self, Error> {
+        Self::create(path.as_ref(), fields, Some(encoding))
+    }
+
+    fn create(
+        path: &Path,
+        fields: Vec<FieldInfo>,
+        encoding: Option<&'static Encoding>,
+    ) -> Result<Self, Error> {
+        let writer = Self::create_part(path, &fields, encoding, None)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            fields,
+            encoding,
+            projection: None,
+            max_shp_size: header::MAX_FILE_SIZE,
+            parts: vec![path.to_path_buf()],
+            writer,
+        })
+    }
+
+    fn create_part(
+        path: &Path,
+        fields: &[FieldInfo],
+        encoding: Option<&'static Encoding>,
+        projection: Option<&Projection>,
+    ) -> Result<FeatureWriter<BufWriter<File>>, Error> {
+        let writer = match encoding {
+            Some(encoding) => {
+                FeatureWriter::from_path_with_encoding(path, fields.to_vec(), encoding)?
+            }
+            None => FeatureWriter::from_path(path, fields.to_vec())?,
+        };
+        if let Some(projection) = projection {
+            projection.write_to_path(path.with_extension("prj"))?;
+        }
+        Ok(writer)
+    }
+
+    /// Writes the projection in the .prj of the parts already created and of the next ones
+    pub fn set_projection(&mut self, projection: &Projection) -> Result<(), Error> {
+        for part in &self.parts {
+            projection.write_to_path(part.with_extension("prj"))?;
+        }
+        self.projection = Some(projection.clone());
+        Ok(())
+    }
+
+    /// Sets the max size in bytes of the .shp of each part,
+    /// which cannot be more than [MAX_FILE_SIZE](../header/constant.MAX_FILE_SIZE.html)
+    pub fn set_max_shp_size(&mut self, max_size: u64) {
+        self.max_shp_size = max_size.min(header::MAX_FILE_SIZE);
+    }
+
+    /// Returns the paths of the .shp of the parts created so far
+    pub fn parts(&self) -> &[PathBuf] {
+        &self.parts
+    }
+
+    /// Writes the shape and its record, in a new part if the shape
+    /// does not fit in the current one
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::FileTooLarge` if the shape alone does not fit in a part,
+    /// and the errors of [FeatureWriter::write_feature](struct.FeatureWriter.html#method.write_feature).
+    pub fn write_feature<S: EsriShape>(
+        &mut self,
+        shape: &S,
+        record: dbase::Record,
+    ) -> Result<(), Error> {
+        let record_size = size_of_record(shape);
+        let shp_size = self.writer.shape_writer.shp_size();
+        if shp_size + record_size > self.max_shp_size {
+            if header::HEADER_SIZE as u64 + record_size > self.max_shp_size {
+                let index = self.writer.shape_writer.shapes_index.len();
+                let offset = Some(header::HEADER_SIZE as u64);
+                return Err(Error::FileTooLarge.with_context(FileRole::Shp, Some(index), offset));
+            }
+            self.start_next_part()?;
+        }
+        self.writer.write_feature(shape, record)
+    }
+
+    /// Finalizes the current part and creates the next one
+    fn start_next_part(&mut self) -> Result<(), Error> {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = format!("{}_{:03}.shp", stem, self.parts.len());
+        let path = self.path.with_file_name(name);
+        let writer =
+            Self::create_part(&path, &self.fields, self.encoding, self.projection.as_ref())?;
+        self.parts.push(path);
+        std::mem::replace(&mut self.writer, writer).finalize()
+    }
+
+    /// Finalizes the last part, and returns the paths of the .shp of all the parts
+    pub fn finalize(self) -> Result<Vec<PathBuf>, Error> {
+        self.writer.finalize()?;
+        Ok(self.parts)
+    }
 }
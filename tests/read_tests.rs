@@ -1,4 +1,8 @@
+#[cfg(feature = "async")]
+extern crate futures;
 extern crate shapefile;
+#[cfg(feature = "async")]
+extern crate tokio;
 
 use std::io::Cursor;
 use std::io::Read;
@@ -12,6 +16,13 @@ use shapefile::{Multipoint, MultipointZ};
 use shapefile::{Polygon, PolygonM, PolygonZ};
 use shapefile::{Polyline, PolylineM, PolylineZ};
 
+use shapefile::record::polygon::GenericPolygon;
+use shapefile::record::polyline::GenericPolyline;
+use shapefile::record::traits::HasXY;
+use shapefile::record::view::{GenericPolygonView, GenericPolylineView, PolygonRingView};
+use shapefile::record::view::{PolygonView, PolylineView, PolylineZView, ViewablePoint};
+use shapefile::{HasShapeType, ReadableShape};
+
 fn check_line<T: Read>(reader: shapefile::Reader<T>) {
     {
         let header = reader.header();
@@ -810,14 +821,945 @@ fn read_point_as_wrong_type() {
     let points = shapefile::read_as::<&str, shapefile::PointM>(testfiles::POINT_PATH);
 
     if let Err(error) = points {
-        match error {
+        match error.without_context() {
             Error::MismatchShapeType {
                 requested: ShapeType::PointM,
                 actual: ShapeType::Point,
             } => {}
             _ => assert!(false),
         }
+        let context = error.context().unwrap();
+        assert_eq!(context.file, shapefile::FileRole::Shp);
+        assert_eq!(context.record_index, Some(0));
+        assert_eq!(context.offset, Some(100));
     } else {
         assert!(false);
     }
 }
+
+#[test]
+fn read_with_sources_of_different_types() {
+    let shp = Cursor::new(std::fs::read(testfiles::POLYGON_HOLE_PATH).unwrap());
+    let shx = std::fs::File::open(testfiles::POLYGON_HOLE_SHX_PATH).unwrap();
+    let mut reader = shapefile::Reader::new(shp).unwrap();
+    reader.add_index_source(shx).unwrap();
+    let polygon = reader.read_nth_shape_as::<Polygon>(0).unwrap();
+    assert!(polygon.is_ok());
+
+    let shp = Cursor::new(std::fs::read(testfiles::MULTIPATCH_PATH).unwrap());
+    let dbf = std::fs::File::open(testfiles::MULTIPATCH_DBF_PATH).unwrap();
+    let reader = shapefile::Reader::new(shp)
+        .unwrap()
+        .with_dbf_source(dbf)
+        .unwrap();
+    let shapes_and_records = reader
+        .iter_shapes_and_records_as::<Multipatch>()
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(shapes_and_records.len(), 1);
+}
+
+#[test]
+fn read_features_by_index() {
+    use shapefile::dbase::{FieldValue, Record};
+    use shapefile::dbf::{FieldInfo, TableWriter};
+    use shapefile::{FeatureWriter, ShapeWriter};
+
+    let mut shp = Cursor::new(Vec::<u8>::new());
+    let mut shx = Cursor::new(Vec::<u8>::new());
+    let mut dbf = Cursor::new(Vec::<u8>::new());
+    {
+        let mut shape_writer = ShapeWriter::new(&mut shp);
+        shape_writer.add_index_dest(&mut shx);
+        let table_writer = TableWriter::new(&mut dbf, vec![FieldInfo::integer("ID")]).unwrap();
+        let mut writer = FeatureWriter::new(shape_writer, table_writer);
+        for id in 0..4 {
+            let mut record = Record::new();
+            record.insert("ID".to_string(), FieldValue::Integer(id));
+            let point = Point::new(f64::from(id), 0.0);
+            writer.write_feature(&point, record).unwrap();
+        }
+    }
+    shp.set_position(0);
+    shx.set_position(0);
+    dbf.set_position(0);
+
+    let mut reader = shapefile::Reader::new(shp).unwrap();
+    reader.add_index_source(shx).unwrap();
+    reader.add_dbf_source(dbf).unwrap();
+
+    let (point, record) = reader.read_nth_feature_as::<Point>(2).unwrap().unwrap();
+    assert_eq!(point, Point::new(2.0, 0.0));
+    assert_eq!(record["ID"], FieldValue::Integer(2));
+    assert!(reader.read_nth_feature(4).is_none());
+
+    let features = reader.read_range_as::<Point>(1..10).unwrap();
+    assert_eq!(features.len(), 3);
+    assert_eq!(features[0].0, Point::new(1.0, 0.0));
+    assert_eq!(features[2].1["ID"], FieldValue::Integer(3));
+    assert_eq!(
+        reader.read_range_as::<Point>(2..usize::MAX).unwrap().len(),
+        2
+    );
+    assert!(reader.read_range(10..20).unwrap().is_empty());
+
+    // Random access does not disturb the sequential reading
+    let shapes_and_records = reader
+        .iter_shapes_and_records_as::<Point>()
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(shapes_and_records.len(), 4);
+    assert_eq!(shapes_and_records[0].1["ID"], FieldValue::Integer(0));
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn read_mmap_polygon_hole() {
+    let mut reader = shapefile::Reader::from_path_mmap(testfiles::POLYGON_HOLE_PATH).unwrap();
+    let first = reader.read_nth_shape_as::<Polygon>(0).unwrap().unwrap();
+    let shapes = reader.read_as::<Polygon>().unwrap();
+    assert_eq!(shapes.len(), 1);
+    assert_eq!(shapes[0], first);
+}
+
+#[cfg(feature = "rstar")]
+#[test]
+fn build_rtree_of_polygon_hole() {
+    use shapefile::record::GenericBBox;
+    use shapefile::rtree::envelope_of;
+
+    let mut reader = shapefile::Reader::from_path(testfiles::POLYGON_HOLE_PATH).unwrap();
+    let tree = reader.build_rtree().unwrap();
+    assert_eq!(tree.size(), 1);
+
+    let shape = reader.read_nth_shape_as::<Polygon>(0).unwrap().unwrap();
+    let bbox = GenericBBox {
+        min: Point::new(shape.bbox().min.x, shape.bbox().min.y),
+        max: Point::new(shape.bbox().max.x, shape.bbox().max.y),
+    };
+    let found = tree
+        .locate_in_envelope_intersecting(&envelope_of(&bbox))
+        .collect::<Vec<_>>();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].index(), 0);
+    assert_eq!(*found[0].bbox(), bbox);
+
+    let center = [
+        (bbox.min.x + bbox.max.x) / 2.0,
+        (bbox.min.y + bbox.max.y) / 2.0,
+    ];
+    assert_eq!(tree.locate_all_at_point(&center).count(), 1);
+    let far_away = [bbox.max.x + 10.0, bbox.max.y + 10.0];
+    assert_eq!(tree.nearest_neighbor(&far_away).unwrap().index(), 0);
+
+    // The reader can still be used after
+    assert_eq!(reader.read_as::<Polygon>().unwrap(), vec![shape]);
+}
+
+#[test]
+fn read_projection() {
+    use shapefile::projection::CoordinateSystemType;
+
+    let wkt = "PROJCS[\"WGS_1984_Web_Mercator_Auxiliary_Sphere\",GEOGCS[\"GCS_WGS_1984\"]]";
+    let mut reader = shapefile::Reader::from_path(testfiles::LINE_PATH).unwrap();
+    assert!(reader.projection().is_none());
+    reader.add_projection_source(wkt.as_bytes()).unwrap();
+
+    let projection = reader.projection().unwrap();
+    assert_eq!(projection.wkt(), wkt);
+    assert_eq!(projection.name(), "WGS_1984_Web_Mercator_Auxiliary_Sphere");
+    assert_eq!(*projection.cs_type(), CoordinateSystemType::Projected);
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn read_layers_of_zip() {
+    extern crate zip;
+    use std::io::Write;
+
+    let path = std::env::temp_dir().join("shapefile_read_layers_of_zip.zip");
+    {
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        let files = [
+            ("lines/LINE.SHP", testfiles::LINE_PATH),
+            ("lines/LINE.SHX", testfiles::LINE_SHX_PATH),
+            ("polygon_hole.shp", testfiles::POLYGON_HOLE_PATH),
+        ];
+        for (name, file) in &files {
+            let options = zip::write::SimpleFileOptions::default();
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(&std::fs::read(file).unwrap()).unwrap();
+        }
+        // The case of the name of the .prj is not the one of the .shp
+        let options = zip::write::SimpleFileOptions::default();
+        writer.start_file("lines/Line.prj", options).unwrap();
+        writer
+            .write_all(b"GEOGCS[\"GCS_WGS_1984\",DATUM[\"D_WGS_1984\"]]")
+            .unwrap();
+        writer.finish().unwrap();
+    }
+
+    assert_eq!(
+        shapefile::archive::layers(&path).unwrap(),
+        vec!["lines/LINE", "polygon_hole"]
+    );
+    match shapefile::Reader::from_zip(&path, None) {
+        Err(shapefile::Error::LayerNotFound { available, .. }) => assert_eq!(available.len(), 2),
+        Err(e) => panic!("Unexpected error: {}", e),
+        Ok(_) => panic!("The layer should not be found"),
+    }
+
+    let mut reader = shapefile::Reader::from_zip(&path, Some("line")).unwrap();
+    assert_eq!(reader.projection().unwrap().name(), "GCS_WGS_1984");
+    let line = reader.read_nth_shape_as::<Polyline>(0).unwrap().unwrap();
+    assert_eq!(reader.read_as::<Polyline>().unwrap(), vec![line]);
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// Writes the polylines, and returns the .shp, the .shx and the offset of the 2nd record
+fn write_polylines(polylines: &[Polyline]) -> (Vec<u8>, Vec<u8>, usize) {
+    let mut shp = Vec::<u8>::new();
+    let mut shx = Vec::<u8>::new();
+    {
+        let mut writer = shapefile::Writer::new(&mut shp);
+        writer.add_index_dest(&mut shx);
+        writer.write_shapes(polylines).unwrap();
+    }
+    let mut offset = [0u8; 4];
+    offset.copy_from_slice(&shx[108..112]);
+    let offset = i32::from_be_bytes(offset) as usize * 2;
+    (shp, shx, offset)
+}
+
+#[test]
+fn errors_tell_where_they_happened() {
+    use shapefile::{Error, FileRole};
+    use std::error::Error as StdError;
+
+    let polylines = (0..3)
+        .map(|i| Polyline::new(vec![Point::new(i as f64, 0.0), Point::new(i as f64, 1.0)]))
+        .collect::<Vec<_>>();
+    let (mut shp, shx, offset) = write_polylines(&polylines);
+    shp[offset + 8..offset + 12].copy_from_slice(&99i32.to_le_bytes());
+
+    let reader = shapefile::Reader::new(Cursor::new(shp.clone())).unwrap();
+    let error = reader.read_as::<Polyline>().unwrap_err();
+    let context = error.context().unwrap();
+    assert_eq!(context.file, FileRole::Shp);
+    assert_eq!(context.record_index, Some(1));
+    assert_eq!(context.offset, Some(offset as u64));
+    match error.without_context() {
+        Error::InvalidShapeType(99) => {}
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(
+        error.to_string(),
+        format!("Error in .shp record 1 at byte {}", offset)
+    );
+    let source = error.source().unwrap();
+    assert_eq!(source.to_string(), Error::InvalidShapeType(99).to_string());
+
+    // Truncated .shx
+    let mut reader = shapefile::Reader::new(Cursor::new(shp)).unwrap();
+    let error = reader.add_index_source(&shx[..110]).unwrap_err();
+    assert_eq!(error.context().unwrap().file, FileRole::Shx);
+    assert_eq!(error.context().unwrap().record_index, Some(1));
+    // The chain of sources gives the wrapped errors, whose messages are not repeated
+    let source = error.source().unwrap();
+    assert_eq!(source.to_string(), "I/O error");
+    let io_error = source.source().unwrap();
+    assert!(io_error.to_string().contains("fill whole buffer"));
+    assert!(!error.to_string().contains("fill whole buffer"));
+}
+
+#[test]
+fn iter_shapes_with_record_info() {
+    use shapefile::record::RecordHeader;
+    use shapefile::RecordInfo;
+
+    let reader = shapefile::Reader::from_path(testfiles::POINTM_PATH).unwrap();
+    let features = reader
+        .iter_shapes_with_info_as::<PointM>()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let points = shapefile::read_as::<_, PointM>(testfiles::POINTM_PATH).unwrap();
+    assert_eq!(features.len(), points.len());
+
+    let mut offset = 100;
+    for (i, (info, point)) in features.iter().enumerate() {
+        assert_eq!(*point, points[i]);
+        // The record number of the test file is not checked
+        let expected = RecordInfo {
+            record_number: info.record_number,
+            offset,
+            content_length: 28,
+        };
+        assert_eq!(*info, expected);
+        offset += (RecordHeader::SIZE + info.content_length) as u64;
+    }
+
+    let hdr = RecordHeader {
+        record_number: 1,
+        record_size: 14,
+    };
+    let mut bytes = Vec::<u8>::new();
+    hdr.write_to(&mut bytes).unwrap();
+    assert_eq!(RecordHeader::read_from(&mut bytes.as_slice()).unwrap(), hdr);
+}
+
+#[test]
+fn read_null_shapes_as_option() {
+    let polylines = (0..3)
+        .map(|i| Polyline::new(vec![Point::new(i as f64, 0.0), Point::new(i as f64, 1.0)]))
+        .collect::<Vec<_>>();
+    let (mut shp, shx, offset) = write_polylines(&polylines);
+    // The 2nd record becomes a Null shape, but keeps its size
+    shp[offset + 8..offset + 12].copy_from_slice(&0i32.to_le_bytes());
+
+    let reader = shapefile::Reader::new(Cursor::new(shp.clone())).unwrap();
+    assert!(reader.read_as::<Polyline>().is_err());
+
+    let reader = shapefile::Reader::new(Cursor::new(shp.clone())).unwrap();
+    let shapes = reader.read_as::<Option<Polyline>>().unwrap();
+    assert_eq!(
+        shapes,
+        vec![Some(polylines[0].clone()), None, Some(polylines[2].clone())]
+    );
+
+    let mut reader = shapefile::Reader::new(Cursor::new(shp)).unwrap();
+    reader.add_index_source(shx.as_slice()).unwrap();
+    let shape = reader.read_nth_shape_as::<Option<Polyline>>(1).unwrap();
+    assert_eq!(shape.unwrap(), None);
+    assert!(reader
+        .read_nth_shape_as::<Option<Point>>(0)
+        .unwrap()
+        .is_err());
+}
+
+#[test]
+fn lenient_iteration_skips_corrupt_records() {
+    let polylines = (0..3)
+        .map(|i| Polyline::new(vec![Point::new(i as f64, 0.0), Point::new(i as f64, 1.0)]))
+        .collect::<Vec<_>>();
+
+    // Unknown shape type in the 2nd record
+    let (mut shp, shx, offset) = write_polylines(&polylines);
+    shp[offset + 8..offset + 12].copy_from_slice(&99i32.to_le_bytes());
+    for with_index in &[false, true] {
+        let mut reader = shapefile::Reader::new(Cursor::new(shp.clone())).unwrap();
+        if *with_index {
+            reader.add_index_source(shx.as_slice()).unwrap();
+        }
+        let mut shapes = reader.iter_shapes_lenient_as::<Polyline>();
+        assert_eq!(
+            (&mut shapes).collect::<Vec<_>>(),
+            vec![polylines[0].clone(), polylines[2].clone()]
+        );
+        let skipped = shapes.into_skipped();
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].record_number, 2);
+        assert_eq!(skipped[0].offset, offset as u64);
+        match skipped[0].error {
+            shapefile::Error::InvalidShapeType(99) => {}
+            ref e => panic!("Unexpected error: {}", e),
+        }
+    }
+
+    // Invalid content length in the 2nd record, only the .shx allows to go to the 3rd
+    let (mut shp, shx, offset) = write_polylines(&polylines);
+    shp[offset + 4..offset + 8].copy_from_slice(&i32::MAX.to_be_bytes());
+    let reader = shapefile::Reader::new(Cursor::new(shp.clone())).unwrap();
+    let mut shapes = reader.iter_shapes_lenient_as::<Polyline>();
+    assert_eq!((&mut shapes).count(), 1);
+    assert_eq!(shapes.skipped().len(), 1);
+
+    let mut reader = shapefile::Reader::new(Cursor::new(shp)).unwrap();
+    reader.add_index_source(shx.as_slice()).unwrap();
+    let mut shapes = reader.iter_shapes_lenient_as::<Polyline>();
+    assert_eq!((&mut shapes).count(), 2);
+    match shapes.skipped()[0].error {
+        shapefile::Error::InvalidShapeRecordSize => {}
+        ref e => panic!("Unexpected error: {}", e),
+    }
+
+    // The file length and the size of the last record are past the end of the file
+    let (mut shp, _, offset) = write_polylines(&polylines);
+    let last_offset = offset + (offset - 100);
+    shp[24..28].copy_from_slice(&i32::MAX.to_be_bytes());
+    shp[last_offset + 4..last_offset + 8].copy_from_slice(&(1i32 << 29).to_be_bytes());
+    let reader = shapefile::Reader::new(Cursor::new(shp)).unwrap();
+    let mut shapes = reader.iter_shapes_lenient_as::<Polyline>();
+    assert_eq!((&mut shapes).count(), 2);
+    assert_eq!(shapes.skipped()[0].offset, last_offset as u64);
+    match shapes.skipped()[0].error {
+        shapefile::Error::InvalidShapeRecordSize => {}
+        ref e => panic!("Unexpected error: {}", e),
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn par_read_shapes_in_record_order() {
+    let polylines = (0..2500)
+        .map(|i| {
+            let points = (0..(i % 7) + 2)
+                .map(|j| Point::new(i as f64, j as f64))
+                .collect::<Vec<_>>();
+            Polyline::new(points)
+        })
+        .collect::<Vec<_>>();
+    let (shp, shx, _) = write_polylines(&polylines);
+
+    // Without .shx, the index is built
+    let mut reader = shapefile::Reader::new(Cursor::new(shp.clone())).unwrap();
+    assert_eq!(reader.par_read_as::<Polyline>().unwrap(), polylines);
+
+    let mut reader = shapefile::Reader::new(Cursor::new(shp.clone())).unwrap();
+    reader.add_index_source(shx.as_slice()).unwrap();
+    assert_eq!(reader.par_read_as::<Polyline>().unwrap(), polylines);
+    assert!(reader.par_read_as::<Point>().is_err());
+
+    // An entry of the .shx that goes past the end of the .shp
+    let mut corrupted = shx.clone();
+    let entry = 100 + 1500 * 8;
+    corrupted[entry..entry + 4].copy_from_slice(&(-4i32).to_be_bytes());
+    let mut reader = shapefile::Reader::new(Cursor::new(shp.clone())).unwrap();
+    reader.add_index_source(corrupted.as_slice()).unwrap();
+    let error = reader.par_read_as::<Polyline>().unwrap_err();
+    match error.without_context() {
+        shapefile::Error::InvalidShapeRecordSize => {}
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(error.context().unwrap().record_index, Some(1500));
+
+    // The .shp declares 4GB, and an entry of the .shx points near its end
+    let mut shp = shp;
+    shp[24..28].copy_from_slice(&i32::MAX.to_be_bytes());
+    let mut corrupted = shx.clone();
+    corrupted[entry..entry + 4].copy_from_slice(&(i32::MAX - 100).to_be_bytes());
+    let mut reader = shapefile::Reader::new(Cursor::new(shp)).unwrap();
+    reader.add_index_source(corrupted.as_slice()).unwrap();
+    let error = reader.par_read_as::<Polyline>().unwrap_err();
+    match error.without_context() {
+        shapefile::Error::IoError(e) => assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof),
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(error.context().unwrap().record_index, Some(1500));
+
+    let mut reader = shapefile::Reader::from_path(testfiles::POLYGON_HOLE_PATH).unwrap();
+    let polygons = reader.par_read_as::<Polygon>().unwrap();
+    let expected = shapefile::read_as::<_, Polygon>(testfiles::POLYGON_HOLE_PATH).unwrap();
+    assert_eq!(polygons, expected);
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_read_shapes_and_records() {
+    use futures::executor::block_on;
+    use futures::{StreamExt, TryFutureExt};
+    use shapefile::async_reader::AsyncReader;
+
+    let reader = shapefile::Reader::from_path(testfiles::MULTIPATCH_PATH).unwrap();
+    let expected = reader.read_as::<Multipatch>().unwrap();
+
+    let shp = std::fs::read(testfiles::MULTIPATCH_PATH).unwrap();
+    let dbf = std::fs::read(testfiles::MULTIPATCH_DBF_PATH).unwrap();
+    let async_reader =
+        block_on(AsyncReader::new(shp.as_slice()).and_then(|r| r.with_dbf_source(dbf.as_slice())))
+            .unwrap();
+    assert_eq!(
+        async_reader.header().shape_type,
+        shapefile::ShapeType::Multipatch
+    );
+    let features = block_on(
+        async_reader
+            .into_shapes_and_records_as::<Multipatch>()
+            .unwrap()
+            .collect::<Vec<_>>(),
+    );
+    assert_eq!(features.len(), expected.len());
+    for (feature, expected_shape) in features.into_iter().zip(expected) {
+        let (shape, record) = feature.unwrap();
+        assert_eq!(shape, expected_shape);
+        assert!(!record.is_empty());
+    }
+
+    let async_reader = block_on(AsyncReader::new(shp.as_slice())).unwrap();
+    match async_reader.into_shapes_and_records() {
+        Err(shapefile::Error::MissingDbf) => {}
+        _ => panic!("The dbf should be missing"),
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_read_record_larger_than_the_file() {
+    use futures::executor::block_on;
+    use futures::StreamExt;
+    use shapefile::async_reader::AsyncReader;
+
+    let polylines = (0..3)
+        .map(|i| Polyline::new(vec![Point::new(i as f64, 0.0), Point::new(i as f64, 1.0)]))
+        .collect::<Vec<_>>();
+    let (mut shp, _, offset) = write_polylines(&polylines);
+    let last_offset = offset + (offset - 100);
+    shp[24..28].copy_from_slice(&i32::MAX.to_be_bytes());
+    shp[last_offset + 4..last_offset + 8].copy_from_slice(&(1i32 << 29).to_be_bytes());
+
+    let async_reader = block_on(AsyncReader::new(shp.as_slice())).unwrap();
+    let shapes = block_on(
+        async_reader
+            .into_shapes_as::<Polyline>()
+            .collect::<Vec<_>>(),
+    );
+    assert_eq!(shapes.len(), 3);
+    assert_eq!(*shapes[1].as_ref().ok().unwrap(), polylines[1]);
+    match shapes[2]
+        .as_ref()
+        .map_err(shapefile::Error::without_context)
+    {
+        Err(shapefile::Error::IoError(e)) => {
+            assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof)
+        }
+        _ => panic!("Expected an UnexpectedEof error"),
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_read_shapes_and_records_after_shape_error() {
+    use futures::executor::block_on;
+    use futures::{StreamExt, TryFutureExt};
+    use shapefile::async_reader::AsyncReader;
+    use shapefile::dbase::{FieldValue, Record};
+    use shapefile::dbf::{FieldInfo, TableWriter};
+    use shapefile::{FeatureWriter, ShapeWriter};
+
+    let mut shp = Cursor::new(Vec::<u8>::new());
+    let mut dbf = Cursor::new(Vec::<u8>::new());
+    {
+        let shape_writer = ShapeWriter::new(&mut shp);
+        let table_writer = TableWriter::new(&mut dbf, vec![FieldInfo::integer("ID")]).unwrap();
+        let mut writer = FeatureWriter::new(shape_writer, table_writer);
+        for id in 0..3 {
+            let mut record = Record::new();
+            record.insert("ID".to_string(), FieldValue::Integer(id));
+            let point = Point::new(f64::from(id), 0.0);
+            writer.write_feature(&point, record).unwrap();
+        }
+    }
+    let mut shp = shp.into_inner();
+    let dbf = dbf.into_inner();
+    // The second record (header of 8 bytes, content of 20 bytes) claims to be a Polyline
+    shp[100 + 28 + 8] = 3;
+
+    let async_reader =
+        block_on(AsyncReader::new(shp.as_slice()).and_then(|r| r.with_dbf_source(dbf.as_slice())))
+            .unwrap();
+    let features = block_on(
+        async_reader
+            .into_shapes_and_records_as::<Point>()
+            .unwrap()
+            .collect::<Vec<_>>(),
+    );
+    assert_eq!(features.len(), 3);
+    assert!(features[1].is_err());
+    let (point, record) = features[2].as_ref().unwrap();
+    assert_eq!(*point, Point::new(2.0, 0.0));
+    assert_eq!(record["ID"], FieldValue::Integer(2));
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_read_shapes_with_invalid_record_size() {
+    use futures::executor::block_on;
+    use futures::StreamExt;
+    use shapefile::async_reader::AsyncReader;
+
+    let mut shp = std::fs::read(testfiles::LINE_PATH).unwrap();
+    // The content length of the first record is way past the end of the file
+    shp[104..108].copy_from_slice(&0x7fff_0000i32.to_be_bytes());
+
+    let reader = block_on(AsyncReader::new(shp.as_slice())).unwrap();
+    let shapes = block_on(reader.into_shapes().collect::<Vec<_>>());
+    assert_eq!(shapes.len(), 1);
+    match shapes[0]
+        .as_ref()
+        .map_err(shapefile::Error::without_context)
+    {
+        Err(shapefile::Error::InvalidShapeRecordSize) => {}
+        _ => panic!("Expected InvalidShapeRecordSize"),
+    }
+}
+
+/// Source that gives one byte at a time, and is not ready every other read
+#[cfg(feature = "async")]
+struct SlowSource<'a> {
+    bytes: &'a [u8],
+    ready: bool,
+}
+
+#[cfg(feature = "async")]
+impl<'a> tokio::io::AsyncRead for SlowSource<'a> {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context,
+        buf: &mut tokio::io::ReadBuf,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        self.ready = !self.ready;
+        if !self.ready {
+            cx.waker().wake_by_ref();
+            return std::task::Poll::Pending;
+        }
+        if let Some((first, rest)) = self.bytes.split_first() {
+            buf.put_slice(&[*first]);
+            self.bytes = rest;
+        }
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_read_shapes_from_slow_source() {
+    use futures::executor::block_on;
+    use futures::StreamExt;
+    use shapefile::async_reader::AsyncReader;
+
+    let expected = shapefile::read_as::<_, Polyline>(testfiles::LINE_PATH).unwrap();
+    let shp = std::fs::read(testfiles::LINE_PATH).unwrap();
+    let source = SlowSource {
+        bytes: &shp,
+        ready: false,
+    };
+    let reader = block_on(AsyncReader::new(source)).unwrap();
+    let shapes = block_on(reader.into_shapes_as::<Polyline>().collect::<Vec<_>>());
+    let shapes = shapes.into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(shapes, expected);
+
+    // A truncated file gives an error, and then the end of the stream
+    let source = SlowSource {
+        bytes: &shp[..shp.len() - 4],
+        ready: false,
+    };
+    let reader = block_on(AsyncReader::new(source)).unwrap();
+    let shapes = block_on(reader.into_shapes().collect::<Vec<_>>());
+    assert_eq!(shapes.len(), expected.len());
+    assert!(shapes.last().unwrap().is_err());
+}
+
+fn check_polyline_views<P>(path: &str)
+where
+    P: ViewablePoint + PartialEq + std::fmt::Debug,
+    GenericPolyline<P>: ReadableShape + HasShapeType,
+{
+    let shapes = shapefile::read_as::<_, GenericPolyline<P>>(path).unwrap();
+    let reader = shapefile::Reader::new(Cursor::new(std::fs::read(path).unwrap())).unwrap();
+    let records = reader
+        .iter_record_bytes()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(records.len(), shapes.len());
+
+    for (record, shape) in records.into_iter().zip(&shapes) {
+        let view = GenericPolylineView::<P>::new(record).unwrap();
+        assert_eq!(&view.bbox(), shape.bbox());
+        assert_eq!(view.total_point_count(), shape.total_point_count());
+        assert_eq!(&view.to_polyline(), shape);
+    }
+}
+
+fn check_polygon_views<P>(path: &str)
+where
+    P: ViewablePoint + HasXY + PartialEq + std::fmt::Debug,
+    GenericPolygon<P>: ReadableShape + HasShapeType,
+{
+    let shapes = shapefile::read_as::<_, GenericPolygon<P>>(path).unwrap();
+    let reader = shapefile::Reader::new(Cursor::new(std::fs::read(path).unwrap())).unwrap();
+    let records = reader
+        .iter_record_bytes()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(records.len(), shapes.len());
+
+    for (record, shape) in records.into_iter().zip(&shapes) {
+        let view = GenericPolygonView::<P>::new(record).unwrap();
+        assert_eq!(&view.bbox(), shape.bbox());
+        let rings = view
+            .rings()
+            .map(|ring| match ring {
+                PolygonRingView::Outer(points) => PolygonRing::Outer(points.to_vec()),
+                PolygonRingView::Inner(points) => PolygonRing::Inner(points.to_vec()),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(rings.as_slice(), shape.rings());
+    }
+}
+
+#[test]
+fn view_polylines() {
+    check_polyline_views::<Point>(testfiles::LINE_PATH);
+    check_polyline_views::<PointM>(testfiles::LINEM_PATH);
+    check_polyline_views::<PointZ>(testfiles::LINEZ_PATH);
+}
+
+#[test]
+fn view_polygons() {
+    check_polygon_views::<Point>(testfiles::POLYGON_PATH);
+    check_polygon_views::<Point>(testfiles::POLYGON_HOLE_PATH);
+    check_polygon_views::<PointM>(testfiles::POLYGONM_PATH);
+    check_polygon_views::<PointZ>(testfiles::POLYGONZ_PATH);
+}
+
+#[test]
+fn view_of_wrong_type() {
+    let bytes = std::fs::read(testfiles::LINE_PATH).unwrap();
+    let reader = shapefile::Reader::new(Cursor::new(bytes)).unwrap();
+    let record = reader.iter_record_bytes().next().unwrap().unwrap();
+    assert!(PolylineZView::new(record).is_err());
+    assert!(PolygonView::new(record).is_err());
+    assert!(PolylineView::new(&record[..record.len() - 1]).is_err());
+}
+
+#[test]
+fn iter_shapes_in_bbox() {
+    use shapefile::record::GenericBBox;
+    use shapefile::ShapeWriter;
+
+    let polylines = vec![
+        Polyline::new(vec![Point::new(0.0, 0.0), Point::new(1.0, 1.0)]),
+        Polyline::new(vec![Point::new(5.0, 5.0), Point::new(6.0, 7.0)]),
+        Polyline::new(vec![Point::new(2.0, -3.0), Point::new(3.0, 4.0)]),
+        Polyline::new(vec![Point::new(-1.0, 10.0), Point::new(1.0, 12.0)]),
+    ];
+    let mut shp = Cursor::new(Vec::<u8>::new());
+    {
+        let mut writer = ShapeWriter::new(&mut shp);
+        for polyline in &polylines {
+            writer.write_shape(polyline).unwrap();
+        }
+    }
+    shp.set_position(0);
+
+    let bbox = GenericBBox {
+        min: Point::new(0.5, 0.5),
+        max: Point::new(5.0, 5.0),
+    };
+    let reader = shapefile::Reader::new(shp).unwrap();
+    let found = reader
+        .iter_shapes_in_bbox_as::<Polyline>(bbox)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        found,
+        vec![
+            polylines[0].clone(),
+            polylines[1].clone(),
+            polylines[2].clone()
+        ]
+    );
+}
+
+#[test]
+fn iter_points_in_bbox() {
+    use shapefile::record::GenericBBox;
+
+    let bbox = GenericBBox {
+        min: Point::new(1.0, 1.0),
+        max: Point::new(1.0, 1.0),
+    };
+    let reader = shapefile::Reader::from_path(testfiles::POINT_PATH).unwrap();
+    let points = reader
+        .iter_shapes_in_bbox_as::<Point>(bbox)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(points.len(), 0);
+
+    let bbox = GenericBBox {
+        min: Point::new(122.0, 37.0),
+        max: Point::new(130.0, 40.0),
+    };
+    let reader = shapefile::Reader::from_path(testfiles::POINT_PATH).unwrap();
+    let points = reader
+        .iter_shapes_in_bbox(bbox)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(points.len(), 1);
+}
+
+#[test]
+fn read_shapes_in_bbox_with_index() {
+    use shapefile::record::GenericBBox;
+
+    let shapes = shapefile::read_as::<_, Polygon>(testfiles::POLYGON_HOLE_PATH).unwrap();
+    let bbox = GenericBBox {
+        min: Point::new(shapes[0].bbox().min.x, shapes[0].bbox().min.y),
+        max: Point::new(shapes[0].bbox().max.x, shapes[0].bbox().max.y),
+    };
+
+    let mut reader = shapefile::Reader::from_path(testfiles::POLYGON_HOLE_PATH).unwrap();
+    let found = reader.read_shapes_in_bbox_as::<Polygon>(bbox).unwrap();
+    assert_eq!(found, vec![(0, shapes[0].clone())]);
+
+    let far_away = GenericBBox {
+        min: Point::new(1000.0, 1000.0),
+        max: Point::new(1001.0, 1001.0),
+    };
+    assert!(reader.read_shapes_in_bbox(far_away).unwrap().is_empty());
+
+    // The reader can still be used after
+    assert_eq!(reader.read_as::<Polygon>().unwrap(), shapes);
+}
+
+#[test]
+fn read_file_declaring_more_than_2gb() {
+    // The length in the header is in 16bit words, 1_500_000_000 words is 3GB
+    let mut content = std::fs::read(testfiles::POINT_PATH).unwrap();
+    content[24..28].copy_from_slice(&1_500_000_000i32.to_be_bytes());
+
+    let reader = shapefile::Reader::new(Cursor::new(content)).unwrap();
+    let results = reader.iter_shapes().collect::<Vec<_>>();
+    assert!(results.last().unwrap().is_err());
+    assert!(results[..results.len() - 1].iter().all(Result::is_ok));
+}
+
+#[test]
+fn invalid_spatial_index_is_ignored() {
+    use shapefile::record::GenericBBox;
+
+    let dir = std::env::temp_dir().join(format!("shapefile_bad_sbn_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let shp_path = dir.join("polygon_hole.shp");
+    std::fs::copy(testfiles::POLYGON_HOLE_PATH, &shp_path).unwrap();
+    std::fs::copy(
+        testfiles::POLYGON_HOLE_SHX_PATH,
+        dir.join("polygon_hole.shx"),
+    )
+    .unwrap();
+    std::fs::write(dir.join("polygon_hole.sbn"), b"not a spatial index").unwrap();
+    std::fs::write(dir.join("polygon_hole.qix"), b"not a quadtree").unwrap();
+
+    let shapes = shapefile::read_as::<_, Polygon>(testfiles::POLYGON_HOLE_PATH).unwrap();
+    let bbox = GenericBBox {
+        min: Point::new(shapes[0].bbox().min.x, shapes[0].bbox().min.y),
+        max: Point::new(shapes[0].bbox().max.x, shapes[0].bbox().max.y),
+    };
+    let mut reader = shapefile::Reader::from_path(&shp_path).unwrap();
+    let found = reader.read_shapes_in_bbox_as::<Polygon>(bbox).unwrap();
+    assert_eq!(found, vec![(0, shapes[0].clone())]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn invalid_projection_is_ignored() {
+    let dir = std::env::temp_dir().join(format!("shapefile_bad_prj_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let shp_path = dir.join("line.shp");
+    std::fs::copy(testfiles::LINE_PATH, &shp_path).unwrap();
+
+    let expected = shapefile::read_as::<_, Polyline>(testfiles::LINE_PATH).unwrap();
+    // Empty, not WKT and not UTF-8
+    for prj in &[&b""[..], &b"EPSG:4326"[..], &[0xff, 0xfe, 0x00][..]] {
+        std::fs::write(dir.join("line.prj"), prj).unwrap();
+        let reader = shapefile::Reader::from_path(&shp_path).unwrap();
+        assert!(reader.projection().is_none());
+        assert_eq!(reader.read_as::<Polyline>().unwrap(), expected);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn negative_record_size_in_bbox_queries() {
+    use shapefile::record::GenericBBox;
+    use shapefile::{Error, FileRole};
+
+    let polylines = (0..3)
+        .map(|i| Polyline::new(vec![Point::new(i as f64, 0.0), Point::new(i as f64, 1.0)]))
+        .collect::<Vec<_>>();
+    let (mut shp, shx, offset) = write_polylines(&polylines);
+    shp[offset + 4..offset + 8].copy_from_slice(&(-1i32).to_be_bytes());
+    let bbox = GenericBBox {
+        min: Point::new(-1.0, -1.0),
+        max: Point::new(10.0, 10.0),
+    };
+
+    let reader = shapefile::Reader::new(Cursor::new(shp.clone())).unwrap();
+    let results = reader.iter_shapes_in_bbox(bbox).collect::<Vec<_>>();
+    match results
+        .last()
+        .unwrap()
+        .as_ref()
+        .map_err(Error::without_context)
+    {
+        Err(Error::InvalidShapeRecordSize) => {}
+        _ => panic!("Expected InvalidShapeRecordSize"),
+    }
+
+    let mut reader = shapefile::Reader::new(Cursor::new(shp)).unwrap();
+    reader.add_index_source(shx.as_slice()).unwrap();
+    let error = match reader.read_shapes_in_bbox(bbox) {
+        Ok(_) => panic!("Expected an error"),
+        Err(e) => e,
+    };
+    match error.without_context() {
+        Error::InvalidShapeRecordSize => {}
+        e => panic!("Unexpected error: {}", e),
+    }
+    let context = error.context().unwrap();
+    assert_eq!(context.file, FileRole::Shp);
+    assert_eq!(context.record_index, Some(1));
+    assert_eq!(context.offset, Some(offset as u64));
+}
+
+#[cfg(feature = "rstar")]
+#[test]
+fn build_rtree_with_negative_record_size() {
+    use shapefile::Error;
+
+    let polylines = (0..3)
+        .map(|i| Polyline::new(vec![Point::new(i as f64, 0.0), Point::new(i as f64, 1.0)]))
+        .collect::<Vec<_>>();
+    let (mut shp, shx, offset) = write_polylines(&polylines);
+    shp[offset + 4..offset + 8].copy_from_slice(&(-1i32).to_be_bytes());
+
+    let mut reader = shapefile::Reader::new(Cursor::new(shp)).unwrap();
+    reader.add_index_source(shx.as_slice()).unwrap();
+    let error = match reader.build_rtree() {
+        Ok(_) => panic!("Expected an error"),
+        Err(e) => e,
+    };
+    match error.without_context() {
+        Error::InvalidShapeRecordSize => {}
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(error.context().unwrap().record_index, Some(1));
+}
+
+#[test]
+fn iter_shapes_with_info_rejects_negative_record_size() {
+    use shapefile::Error;
+
+    let polylines = (0..3)
+        .map(|i| Polyline::new(vec![Point::new(i as f64, 0.0), Point::new(i as f64, 1.0)]))
+        .collect::<Vec<_>>();
+    let (mut shp, _, offset) = write_polylines(&polylines);
+    shp[offset + 4..offset + 8].copy_from_slice(&(-1i32).to_be_bytes());
+
+    let reader = shapefile::Reader::new(Cursor::new(shp)).unwrap();
+    let results = reader
+        .iter_shapes_with_info_as::<Polyline>()
+        .collect::<Vec<_>>();
+    assert_eq!(results.len(), 2);
+    assert_eq!(
+        results[0].as_ref().ok().unwrap().0.content_length,
+        offset - 108
+    );
+    match results[1].as_ref().map_err(Error::without_context) {
+        Err(Error::InvalidShapeRecordSize) => {}
+        _ => panic!("Expected InvalidShapeRecordSize"),
+    }
+}
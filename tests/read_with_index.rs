@@ -15,3 +15,29 @@ fn test_line() {
 
     assert_eq!(reader.read_nth_shape(1).is_none(), true);
 }
+
+#[test]
+fn index_is_built_when_no_shx() {
+    // pointz.shp has no .shx
+    let mut reader = shapefile::Reader::from_path(testfiles::POINTZ_PATH).unwrap();
+    let first = reader.read_nth_shape(0).unwrap().unwrap();
+    let shapes = shapefile::read(testfiles::POINTZ_PATH).unwrap();
+    assert_eq!(first.to_string(), shapes[0].to_string());
+    assert!(reader.read_nth_shape(shapes.len()).is_none());
+}
+
+#[test]
+fn rebuild_shx() {
+    for (shp_path, shx_path) in &[
+        (testfiles::LINE_PATH, testfiles::LINE_SHX_PATH),
+        (
+            testfiles::POLYGON_HOLE_PATH,
+            testfiles::POLYGON_HOLE_SHX_PATH,
+        ),
+    ] {
+        let shp = std::fs::File::open(shp_path).unwrap();
+        let mut shx = Vec::<u8>::new();
+        shapefile::index::rebuild(shp, &mut shx).unwrap();
+        assert_eq!(shx, std::fs::read(shx_path).unwrap());
+    }
+}
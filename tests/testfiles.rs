@@ -23,6 +23,7 @@ pub const MULTIPOINT_PATH: &str = "./tests/data/multipoint.shp";
 pub const MULTIPOINTZ_PATH: &str = "./tests/data/multipointz.shp";
 
 pub const MULTIPATCH_PATH: &str = "./tests/data/multipatch.shp";
+pub const MULTIPATCH_DBF_PATH: &str = "./tests/data/multipatch.dbf";
 
 pub fn check_line_first_shape(shape: &shapefile::Shape) {
     if let shapefile::Shape::Polyline(shp) = shape {
@@ -1,8 +1,17 @@
+#[cfg(feature = "async")]
+extern crate futures;
 extern crate shapefile;
 
 mod testfiles;
 
-use shapefile::{Point, Polygon, PolygonRing, Polyline, Writer};
+use std::io::Cursor;
+
+use shapefile::dbase::{FieldValue, Record};
+use shapefile::dbf::{FieldInfo, TableWriter};
+use shapefile::{
+    Error, FeatureWriter, MultipointZ, Point, Polygon, PolygonRing, Polyline, Shape, ShapeType,
+    ShapeWriter, Writer,
+};
 
 fn read_a_file(path: &str) -> std::io::Result<Vec<u8>> {
     use std::io::Read;
@@ -125,3 +134,489 @@ fn polygon_inner_is_correctly_reordered() {
     assert_eq!(expected.is_ok(), true);
     assert_eq!(shx, expected.unwrap());
 }
+
+#[test]
+fn streamed_single_point() {
+    let mut shp = Cursor::new(Vec::<u8>::new());
+    let mut shx = Cursor::new(Vec::<u8>::new());
+    {
+        let mut writer = ShapeWriter::new(&mut shp);
+        writer.add_index_dest(&mut shx);
+        writer.write_shape(&Point::new(122.0, 37.0)).unwrap();
+        writer.finalize().unwrap();
+    }
+
+    let expected = read_a_file(testfiles::POINT_PATH);
+    assert_eq!(shp.into_inner(), expected.unwrap());
+
+    let expected = read_a_file(testfiles::POINT_SHX_PATH);
+    assert_eq!(shx.into_inner(), expected.unwrap());
+}
+
+#[test]
+fn streamed_shapes_are_finalized_on_drop() {
+    let polylines = vec![
+        Polyline::new(vec![Point::new(1.0, 5.0), Point::new(5.0, 5.0)]),
+        Polyline::new(vec![Point::new(-3.0, 2.0), Point::new(2.0, 6.0)]),
+    ];
+
+    let mut streamed_shp = Cursor::new(Vec::<u8>::new());
+    let mut streamed_shx = Cursor::new(Vec::<u8>::new());
+    {
+        let mut writer = ShapeWriter::new(&mut streamed_shp);
+        writer.add_index_dest(&mut streamed_shx);
+        for polyline in &polylines {
+            writer.write_shape(polyline).unwrap();
+        }
+    }
+
+    let mut shp: Vec<u8> = vec![];
+    let mut shx: Vec<u8> = vec![];
+    let mut writer = Writer::new(&mut shp);
+    writer.add_index_dest(&mut shx);
+    writer.write_shapes(&polylines).unwrap();
+
+    assert_eq!(streamed_shp.into_inner(), shp);
+    assert_eq!(streamed_shx.into_inner(), shx);
+}
+
+#[test]
+fn streamed_shapes_must_have_the_same_type() {
+    let mut shp = Cursor::new(Vec::<u8>::new());
+    let mut writer = ShapeWriter::new(&mut shp);
+    writer.write_shape(&Point::new(1.0, 1.0)).unwrap();
+    let polyline = Polyline::new(vec![Point::new(1.0, 5.0), Point::new(5.0, 5.0)]);
+    assert!(writer.write_shape(&polyline).is_err());
+}
+
+/// Cursor whose writes fail once, when they start at or after `fail_at`
+struct FailingCursor {
+    inner: Cursor<Vec<u8>>,
+    fail_at: Option<u64>,
+}
+
+impl std::io::Write for FailingCursor {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.fail_at {
+            Some(fail_at) if self.inner.position() >= fail_at => {
+                self.fail_at = None;
+                Err(std::io::Error::other("disk full"))
+            }
+            _ => self.inner.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl std::io::Seek for FailingCursor {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[test]
+fn shapes_that_failed_to_be_written_are_not_indexed() {
+    let point = Point::new(1.0, 1.0);
+    // Header of the file, and header and content of a point record
+    let second_record = 100 + 8 + 20;
+    let mut shp = FailingCursor {
+        inner: Cursor::new(Vec::new()),
+        fail_at: Some(second_record),
+    };
+    let mut shx = FailingCursor {
+        inner: Cursor::new(Vec::new()),
+        fail_at: None,
+    };
+    {
+        let mut writer = ShapeWriter::new(&mut shp);
+        writer.add_index_dest(&mut shx);
+        writer.write_shape(&point).unwrap();
+        assert!(writer.write_shape(&point).is_err());
+        writer.write_shape(&point).unwrap();
+        writer.finalize().unwrap();
+    }
+
+    let (shp, shx) = (shp.inner.into_inner(), shx.inner.into_inner());
+    assert_eq!(shx.len(), 100 + 2 * 8);
+    let mut reader = shapefile::Reader::new(Cursor::new(shp)).unwrap();
+    reader.add_index_source(Cursor::new(shx)).unwrap();
+    assert_eq!(
+        reader.read_nth_shape_as::<Point>(1).unwrap().unwrap(),
+        point
+    );
+    assert!(reader.read_nth_shape_as::<Point>(2).is_none());
+}
+
+#[test]
+fn null_shapes_are_written() {
+    let polylines = vec![
+        None,
+        Some(Polyline::new(vec![
+            Point::new(1.0, 5.0),
+            Point::new(5.0, 5.0),
+        ])),
+        None,
+        Some(Polyline::new(vec![
+            Point::new(-3.0, 2.0),
+            Point::new(2.0, 6.0),
+        ])),
+    ];
+
+    let mut shp: Vec<u8> = vec![];
+    let mut shx: Vec<u8> = vec![];
+    let mut writer = Writer::new(&mut shp);
+    writer.add_index_dest(&mut shx);
+    writer.write_shapes(&polylines).unwrap();
+
+    let mut streamed_shp = Cursor::new(Vec::<u8>::new());
+    let mut streamed_shx = Cursor::new(Vec::<u8>::new());
+    {
+        let mut writer = ShapeWriter::new(&mut streamed_shp);
+        writer.add_index_dest(&mut streamed_shx);
+        for polyline in &polylines {
+            writer.write_shape(polyline).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+    assert_eq!(streamed_shp.into_inner(), shp);
+    assert_eq!(streamed_shx.into_inner(), shx);
+
+    let reader = shapefile::Reader::new(Cursor::new(shp.clone())).unwrap();
+    assert_eq!(reader.read_as::<Option<Polyline>>().unwrap(), polylines);
+
+    let mut reader = shapefile::Reader::new(Cursor::new(shp)).unwrap();
+    reader.add_index_source(Cursor::new(shx)).unwrap();
+    assert_eq!(reader.header().shape_type, shapefile::ShapeType::Polyline);
+    assert_eq!(reader.header().bbox.min.x, -3.0);
+    assert_eq!(reader.header().bbox.max.y, 6.0);
+    let null_record = reader.read_nth_shape_as::<Option<Polyline>>(2).unwrap();
+    assert_eq!(null_record.unwrap(), None);
+    let content_lengths = reader
+        .iter_shapes_with_info_as::<Option<Polyline>>()
+        .map(|result| result.unwrap().0.content_length)
+        .collect::<Vec<_>>();
+    assert_eq!(content_lengths, vec![4, 80, 4, 80]);
+}
+
+#[test]
+fn only_null_shapes_have_an_empty_bbox() {
+    let polylines: Vec<Option<Polyline>> = vec![None, None];
+
+    let mut shp: Vec<u8> = vec![];
+    let mut shx: Vec<u8> = vec![];
+    let mut writer = Writer::new(&mut shp);
+    writer.add_index_dest(&mut shx);
+    writer.write_shapes(&polylines).unwrap();
+
+    let mut streamed_shp = Cursor::new(Vec::<u8>::new());
+    {
+        let mut writer = ShapeWriter::new(&mut streamed_shp);
+        for polyline in &polylines {
+            writer.write_shape(polyline).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+    assert_eq!(streamed_shp.into_inner(), shp);
+
+    let reader = shapefile::Reader::new(Cursor::new(shp)).unwrap();
+    let bbox = reader.header().bbox;
+    assert_eq!(bbox.min, shapefile::PointZ::default());
+    assert_eq!(bbox.max, shapefile::PointZ::default());
+    assert_eq!([bbox.min.x, bbox.min.y, bbox.max.x, bbox.max.y], [0.0; 4]);
+}
+
+fn write_to_vec<S: shapefile::record::EsriShape>(
+    shapes: &[S],
+) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let mut shp: Vec<u8> = vec![];
+    let mut shx: Vec<u8> = vec![];
+    {
+        let mut writer = Writer::new(&mut shp);
+        writer.add_index_dest(&mut shx);
+        writer.write_shapes(shapes)?;
+    }
+    Ok((shp, shx))
+}
+
+#[test]
+fn shapes_of_the_enum_are_written_like_concrete_shapes() {
+    let shapes = shapefile::read(testfiles::LINE_PATH).unwrap();
+    let polylines = shapefile::read_as::<_, Polyline>(testfiles::LINE_PATH).unwrap();
+    assert_eq!(
+        write_to_vec(&shapes).unwrap(),
+        write_to_vec(&polylines).unwrap()
+    );
+
+    let shapes = shapefile::read(testfiles::MULTIPOINTZ_PATH).unwrap();
+    let multipoints = shapefile::read_as::<_, MultipointZ>(testfiles::MULTIPOINTZ_PATH).unwrap();
+    assert_eq!(
+        write_to_vec(&shapes).unwrap(),
+        write_to_vec(&multipoints).unwrap()
+    );
+
+    let mut shapes = shapefile::read(testfiles::POLYGON_HOLE_PATH).unwrap();
+    shapes.insert(0, Shape::NullShape);
+    let mut polygons = shapefile::read_as::<_, Polygon>(testfiles::POLYGON_HOLE_PATH)
+        .unwrap()
+        .into_iter()
+        .map(Some)
+        .collect::<Vec<_>>();
+    polygons.insert(0, None);
+    assert_eq!(
+        write_to_vec(&shapes).unwrap(),
+        write_to_vec(&polygons).unwrap()
+    );
+}
+
+#[test]
+fn shapes_of_the_enum_must_have_the_same_type() {
+    let shapes = vec![
+        Shape::NullShape,
+        Shape::Point(Point::new(1.0, 1.0)),
+        Shape::Polyline(Polyline::new(vec![
+            Point::new(1.0, 5.0),
+            Point::new(5.0, 5.0),
+        ])),
+    ];
+    let error = write_to_vec(&shapes).expect_err("Shapes of different types were written");
+    match error.without_context() {
+        Error::MismatchShapeType { requested, actual } => {
+            assert_eq!(*requested, ShapeType::Point);
+            assert_eq!(*actual, ShapeType::Polyline);
+        }
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(error.context().unwrap().record_index, Some(2));
+
+    let (shp, _) = write_to_vec(&[Shape::NullShape]).unwrap();
+    let reader = shapefile::Reader::new(Cursor::new(shp)).unwrap();
+    assert_eq!(reader.header().shape_type, ShapeType::NullShape);
+}
+
+/// Shape that pretends to be huge, to check the size limit without writing gigabytes
+struct HugeShape;
+
+impl shapefile::HasShapeType for HugeShape {
+    fn shapetype() -> ShapeType {
+        ShapeType::Polyline
+    }
+}
+
+impl shapefile::record::WritableShape for HugeShape {
+    fn size_in_bytes(&self) -> usize {
+        i32::MAX as usize
+    }
+
+    fn write_to<T: std::io::Write>(&self, _dest: &mut T) -> Result<(), Error> {
+        unreachable!("Huge shapes are never written")
+    }
+}
+
+impl shapefile::record::EsriShape for HugeShape {
+    fn x_range(&self) -> [f64; 2] {
+        [0.0, 0.0]
+    }
+
+    fn y_range(&self) -> [f64; 2] {
+        [0.0, 0.0]
+    }
+}
+
+#[test]
+fn too_large_shapefiles_are_not_written() {
+    let mut shp: Vec<u8> = vec![];
+    let error = Writer::new(&mut shp)
+        .write_shapes(&[HugeShape, HugeShape])
+        .unwrap_err();
+    assert!(matches!(error.without_context(), Error::FileTooLarge));
+    assert_eq!(error.context().unwrap().record_index, Some(1));
+    assert!(shp.is_empty());
+}
+
+#[test]
+fn split_features_in_several_shapefiles() {
+    use shapefile::projection::Projection;
+    use shapefile::SplittingWriter;
+
+    let dir = std::env::temp_dir().join(format!("shapefile_split_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let projection = Projection::from_wkt(r#"GEOGCS["GCS_WGS_1984",DATUM["D_WGS_1984",SPHEROID["WGS_1984",6378137.0,298.257223563]],PRIMEM["Greenwich",0.0],UNIT["Degree",0.0174532925199433]]"#).unwrap();
+
+    let fields = vec![FieldInfo::numeric("ID", 4, 0).unwrap()];
+    let mut writer = SplittingWriter::from_path(dir.join("points.shp"), fields).unwrap();
+    // A point record takes 28 bytes
+    writer.set_max_shp_size(100 + 2 * 28);
+    writer.set_projection(&projection).unwrap();
+    for i in 0..5 {
+        let mut record = Record::new();
+        record.insert("ID".to_string(), FieldValue::Numeric(Some(i as f64)));
+        writer
+            .write_feature(&Point::new(i as f64, 0.0), record)
+            .unwrap();
+    }
+    let line = Polyline::new(vec![Point::new(0.0, 0.0), Point::new(1.0, 1.0)]);
+    let error = writer.write_feature(&line, Record::new()).unwrap_err();
+    assert!(matches!(error.without_context(), Error::FileTooLarge));
+    let parts = writer.finalize().unwrap();
+
+    let names = parts
+        .iter()
+        .map(|part| part.file_name().unwrap().to_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec!["points.shp", "points_001.shp", "points_002.shp"]
+    );
+    let mut ids = vec![];
+    for part in &parts {
+        let reader = shapefile::Reader::from_path(part).unwrap();
+        assert_eq!(reader.projection(), Some(&projection));
+        for feature in reader.iter_shapes_and_records_as::<Point>().unwrap() {
+            let (point, record) = feature.unwrap();
+            assert_eq!(record["ID"], FieldValue::Numeric(Some(point.x)));
+            ids.push(point.x);
+        }
+    }
+    assert_eq!(ids, vec![0.0, 1.0, 2.0, 3.0, 4.0]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_written_shapes_are_the_same_as_written_shapes() {
+    use futures::executor::block_on;
+    use shapefile::async_writer::AsyncWriter;
+
+    let polylines = vec![
+        Polyline::new(vec![Point::new(1.0, 5.0), Point::new(5.0, 5.0)]),
+        Polyline::new(vec![Point::new(-3.0, 2.0), Point::new(2.0, 6.0)]),
+    ];
+
+    // The shapefile does not start at the beginning of the dest
+    let mut async_shp = Cursor::new(vec![0u8; 5]);
+    async_shp.set_position(5);
+    let mut async_shx = Cursor::new(Vec::<u8>::new());
+    {
+        let mut writer = AsyncWriter::new(&mut async_shp);
+        writer.add_index_dest(&mut async_shx);
+        for polyline in &polylines {
+            block_on(writer.write_shape(polyline)).unwrap();
+        }
+        let point = Point::new(1.0, 1.0);
+        assert!(block_on(writer.write_shape(&point)).is_err());
+        block_on(writer.finalize()).unwrap();
+    }
+
+    let mut shp: Vec<u8> = vec![];
+    let mut shx: Vec<u8> = vec![];
+    let mut writer = Writer::new(&mut shp);
+    writer.add_index_dest(&mut shx);
+    writer.write_shapes(&polylines).unwrap();
+
+    assert_eq!(async_shp.position(), 5 + shp.len() as u64);
+    assert_eq!(&async_shp.into_inner()[5..], shp.as_slice());
+    assert_eq!(async_shx.into_inner(), shx);
+}
+
+#[test]
+fn features_are_written_with_their_records() {
+    let fields = vec![
+        FieldInfo::character("NAME", 10).unwrap(),
+        FieldInfo::numeric("ELEVATION", 8, 2).unwrap(),
+        FieldInfo::logical("VISITED"),
+    ];
+    let mut shp = Cursor::new(Vec::<u8>::new());
+    let mut dbf = Cursor::new(Vec::<u8>::new());
+    {
+        let shape_writer = ShapeWriter::new(&mut shp);
+        let table_writer = TableWriter::new(&mut dbf, fields).unwrap();
+        let mut writer = FeatureWriter::new(shape_writer, table_writer);
+
+        for (i, name) in ["Mont Blanc", "Aneto"].iter().enumerate() {
+            let mut record = Record::new();
+            record.insert(
+                "NAME".to_string(),
+                FieldValue::Character(Some(name.to_string())),
+            );
+            record.insert(
+                "ELEVATION".to_string(),
+                FieldValue::Numeric(Some(3000.5 + i as f64)),
+            );
+            record.insert("VISITED".to_string(), FieldValue::Logical(None));
+            writer
+                .write_feature(&Point::new(i as f64, 0.0), record)
+                .unwrap();
+        }
+
+        let mut record = Record::new();
+        record.insert("NAME".to_string(), FieldValue::Numeric(Some(1.0)));
+        assert!(writer.write_feature(&Point::new(2.0, 0.0), record).is_err());
+        writer.finalize().unwrap();
+    }
+
+    shp.set_position(0);
+    dbf.set_position(0);
+    let mut reader = shapefile::Reader::new(shp).unwrap();
+    reader.add_dbf_source(dbf).unwrap();
+    let features = reader
+        .iter_shapes_and_records_as::<Point>()
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(features.len(), 2);
+    let (point, record) = &features[1];
+    assert_eq!(*point, Point::new(1.0, 0.0));
+    assert_eq!(
+        record["NAME"],
+        FieldValue::Character(Some("Aneto".to_string()))
+    );
+    assert_eq!(record["ELEVATION"], FieldValue::Numeric(Some(3001.5)));
+    assert_eq!(record["VISITED"], FieldValue::Logical(None));
+}
+
+#[test]
+fn qix_is_written_with_the_shapes() {
+    use shapefile::record::GenericBBox;
+
+    let points = (0..100)
+        .map(|i| Point::new((i % 10) as f64, (i / 10) as f64))
+        .collect::<Vec<_>>();
+    let mut shp: Vec<u8> = vec![];
+    let mut shx: Vec<u8> = vec![];
+    let mut qix: Vec<u8> = vec![];
+    let mut writer = Writer::new(&mut shp);
+    writer.add_index_dest(&mut shx);
+    writer.add_qix_dest(&mut qix);
+    writer.write_shapes(&points).unwrap();
+    assert_eq!(&qix[..3], b"SQT");
+
+    let mut reader = shapefile::Reader::new(Cursor::new(shp)).unwrap();
+    reader.add_index_source(Cursor::new(shx)).unwrap();
+    reader.add_quad_tree_source(Cursor::new(qix)).unwrap();
+    let bbox = GenericBBox {
+        min: Point::new(1.5, 1.5),
+        max: Point::new(3.0, 2.0),
+    };
+    let found = reader.read_shapes_in_bbox_as::<Point>(bbox).unwrap();
+    assert_eq!(found, vec![(22, points[22]), (23, points[23])]);
+}
+
+#[test]
+fn shapes_and_records_must_have_the_same_length() {
+    let mut shp: Vec<u8> = vec![];
+    let writer = Writer::new(&mut shp);
+    let points = vec![Point::new(1.0, 2.0), Point::new(3.0, 4.0)];
+    match writer.write_shapes_and_records(&points, vec![Record::new()]) {
+        Err(Error::MismatchRecordCount {
+            shapes: 2,
+            records: 1,
+        }) => {}
+        Err(e) => panic!("Unexpected error: {}", e),
+        Ok(_) => panic!("The lengths should not match"),
+    }
+    assert!(shp.is_empty());
+}